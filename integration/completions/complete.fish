@@ -0,0 +1,8 @@
+
+# Complete bookmark names as the query of the browser and of `copy`
+complete -c shellmark -n "__fish_seen_subcommand_from copy browse b" -f -a "(shellmark completions --bookmark-names 2>/dev/null)" -d Bookmark
+complete -c shellmark -n "not __fish_seen_subcommand_from {subcommands}" -f -a "(shellmark completions --bookmark-names 2>/dev/null)" -d Bookmark
+
+for cmd in {alias}
+    complete -c $cmd -w shellmark
+end
@@ -0,0 +1,47 @@
+
+# Complete bookmark names as the query of the browser and of `copy`, everything else as above
+$__shellmarkCompleter = {
+    param($wordToComplete, $commandAst, $cursorPosition)
+
+    $subcommand = $null
+    $elements = $commandAst.CommandElements
+    for ($i = 1; $i -lt $elements.Count; $i++) {
+        $word = "$($elements[$i])"
+        if ($word -eq $wordToComplete -and $i -eq $elements.Count - 1) {
+            break
+        }
+        if ($word -in '-o', '--out') {
+            $i++
+        } elseif (-not $word.StartsWith('-')) {
+            $subcommand = $word
+            break
+        }
+    }
+
+    $previous = if ($wordToComplete) { $elements[-2] } else { $elements[-1] }
+    if ($wordToComplete.StartsWith('-') -or "$previous" -in '-o', '--out', '--height', '--opener') {
+        & $__shellmarkClapCompleter $wordToComplete $commandAst $cursorPosition
+        return
+    }
+
+    # The first word is a subcommand or the query, anything that isn't a subcommand is the query
+    $commands = '{subcommands}' -split ' '
+    if ($null -eq $subcommand) {
+        & $__shellmarkClapCompleter $wordToComplete $commandAst $cursorPosition
+    } elseif ($subcommand -notin 'copy', 'browse', 'b' -and $subcommand -in $commands) {
+        & $__shellmarkClapCompleter $wordToComplete $commandAst $cursorPosition
+        return
+    }
+
+    shellmark completions --bookmark-names 2>$null |
+        Where-Object { $_.StartsWith($wordToComplete) } |
+        ForEach-Object {
+            $text = $_
+            if ($text -match '[\s''"`$;,(){}@&|<>#]') {
+                $text = "'" + ($text -replace "'", "''") + "'"
+            }
+            [CompletionResult]::new($text, $_, [CompletionResultType]::ParameterValue, $_)
+        }
+}.GetNewClosure()
+
+Register-ArgumentCompleter -Native -CommandName @('shellmark'{alias}) -ScriptBlock $__shellmarkCompleter
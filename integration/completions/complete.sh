@@ -0,0 +1,41 @@
+
+# Complete bookmark names as the query of the browser and of `copy`, everything else as above
+_shellmark_bookmarks() {
+    local i subcmd=""
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            -o|--out) ((i++)) ;;
+            -*) ;;
+            *) subcmd="${COMP_WORDS[i]}"; break ;;
+        esac
+    done
+
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    case "$cur $prev" in
+        -*|*" -o"|*" --out"|*" --height"|*" --opener")
+            _shellmark "$@"
+            return
+            ;;
+    esac
+
+    # The first word is a subcommand or the query, anything that isn't a subcommand is the query
+    local commands=" {subcommands} "
+    COMPREPLY=()
+    if [[ -z $subcmd ]]; then
+        _shellmark "$@"
+    elif [[ $subcmd != @(copy|browse|b) && $commands == *" $subcmd "* ]]; then
+        _shellmark "$@"
+        return
+    fi
+
+    local name
+    while IFS= read -r name; do
+        printf -v name '%q' "$name"
+        if [[ $name == "$cur"* ]]; then
+            COMPREPLY+=("$name")
+        fi
+    done < <(shellmark completions --bookmark-names 2>/dev/null)
+    return 0
+}
+
+complete -F _shellmark_bookmarks -o bashdefault -o default shellmark {alias}
@@ -6,9 +6,27 @@ function {name}
         return 1
     end
 
-    set -l out (shellmark --out fish $argv)
+    # Without `string collect` the lines would be joined with spaces, a path can have newlines
+    set -l out (shellmark --out fish $argv | string collect)
 
-    if test -n "Sout"
+    if test -n "$out"
         eval "$out"
     end
-end
\ No newline at end of file
+end
+
+# Ctrl-G inserts the path of a bookmark at the cursor
+function __{name}_insert_path
+    set -l selected (shellmark --out fish browse --insert | string collect)
+    if test -n "$selected"
+        commandline -i -- "$selected"
+    end
+    commandline -f repaint
+end
+
+bind \cg __{name}_insert_path
+if bind -M insert >/dev/null 2>&1
+    bind -M insert \cg __{name}_insert_path
+end
+
+# Tab completes the subcommands and the names of bookmarks
+shellmark completions fish --alias {name} | source
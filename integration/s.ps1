@@ -3,7 +3,8 @@ function Invoke-Shellmark {
         Write-Output "shellmark not found in path"
         return
     }
-    $OUT = shellmark --out powershell @args
+    # Output with several lines comes as an array, a path can have newlines
+    $OUT = @(shellmark --out powershell @args) -join "`n"
     if ($OUT) {
         try {
             Invoke-Expression $OUT
@@ -14,4 +15,18 @@ function Invoke-Shellmark {
     }
 }
 
-Set-Alias -Name {name} -Value Invoke-Shellmark
\ No newline at end of file
+Set-Alias -Name {name} -Value Invoke-Shellmark
+
+# Ctrl-G inserts the path of a bookmark at the cursor
+if (Get-Module PSReadLine) {
+    Set-PSReadLineKeyHandler -Chord Ctrl+g -ScriptBlock {
+        $selected = @(shellmark --out powershell browse --insert) -join "`n"
+        if ($selected) {
+            [Microsoft.PowerShell.PSConsoleReadLine]::Insert($selected)
+        }
+        [Microsoft.PowerShell.PSConsoleReadLine]::InvokePrompt()
+    }
+}
+
+# Tab completes the subcommands and the names of bookmarks
+Invoke-Expression (@(&shellmark completions powershell --alias {name}) -join "`n")
@@ -12,4 +12,37 @@ function {name} {
     if [[ -n $out ]]; then
         eval "$out"
     fi
-}
\ No newline at end of file
+}
+
+# Ctrl-G inserts the path of a bookmark at the cursor
+if [[ -n $ZSH_VERSION ]]; then
+    function __{name}_insert_path {
+        local selected
+        selected="$(shellmark --out posix browse --insert)"
+        if [[ -n $selected ]]; then
+            LBUFFER="${LBUFFER}${selected}"
+        fi
+        zle reset-prompt
+    }
+    zle -N __{name}_insert_path
+    bindkey '^G' __{name}_insert_path
+elif [[ -n $BASH_VERSION && $- == *i* ]]; then
+    function __{name}_insert_path {
+        local selected
+        selected="$(shellmark --out posix browse --insert)"
+        if [[ -n $selected ]]; then
+            READLINE_LINE="${READLINE_LINE:0:$READLINE_POINT}${selected}${READLINE_LINE:$READLINE_POINT}"
+            READLINE_POINT=$((READLINE_POINT + ${#selected}))
+        fi
+    }
+    bind -x '"\C-g": __{name}_insert_path'
+fi
+
+# Tab completes the subcommands and the names of bookmarks
+if [[ -n $ZSH_VERSION ]]; then
+    if type compdef &>/dev/null; then
+        eval "$(shellmark completions zsh --alias {name})"
+    fi
+elif [[ -n $BASH_VERSION ]]; then
+    eval "$(shellmark completions bash --alias {name})"
+fi
@@ -0,0 +1,6 @@
+
+# Count every directory the shell changes to as a visit, to rank bookmarks by
+function __{name}_track --on-variable PWD
+    command shellmark visit "$PWD" >/dev/null 2>&1 &
+    disown 2>/dev/null
+end
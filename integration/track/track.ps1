@@ -0,0 +1,11 @@
+
+# Count every directory the shell changes to as a visit, to rank bookmarks by
+$global:__shellmarkLastDir = $PWD.ProviderPath
+$global:__shellmarkPrompt = $function:prompt
+function global:prompt {
+    if ($PWD.Provider.Name -eq 'FileSystem' -and $PWD.ProviderPath -ne $global:__shellmarkLastDir) {
+        $global:__shellmarkLastDir = $PWD.ProviderPath
+        shellmark visit $PWD.ProviderPath *> $null
+    }
+    & $global:__shellmarkPrompt
+}
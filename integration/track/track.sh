@@ -0,0 +1,18 @@
+
+# Count every directory the shell changes to as a visit, to rank bookmarks by
+if [[ -n $ZSH_VERSION ]]; then
+    function __{name}_track {
+        (shellmark visit "$PWD" &>/dev/null &)
+    }
+    autoload -Uz add-zsh-hook
+    add-zsh-hook chpwd __{name}_track
+elif [[ -n $BASH_VERSION ]]; then
+    __{name}_last_dir=$PWD
+    function __{name}_track {
+        if [[ $PWD != "$__{name}_last_dir" ]]; then
+            __{name}_last_dir=$PWD
+            (shellmark visit "$PWD" &>/dev/null &)
+        fi
+    }
+    PROMPT_COMMAND="__{name}_track${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi
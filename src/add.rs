@@ -1,7 +1,10 @@
-use std::env;
+use std::{env, path::Path};
 
 use crate::{
-    bookmarks::{read_bookmarks, write_bookmarks, Bookmark},
+    bookmarks::{
+        find_project_file, read_bookmarks, read_personal_bookmarks, write_bookmarks, Bookmark,
+        Source, PROJECT_FILE,
+    },
     cli,
     storage::friendly_path,
 };
@@ -14,34 +17,43 @@ pub async fn add_cmd(add_cmd_opts: cli::AddCmd) -> Result<()> {
         Some(path_str) => fs::canonicalize(&path_str).await?,
         None => env::current_dir()?,
     };
-    let name = add_cmd_opts.name.unwrap_or_else(|| {
-        // It's possible that the path is a root path (`/` or `C:\`) and file name N/A.
-        // In this case just use dest's friendly path
-        dest.file_name()
-            .map(|f| f.to_string_lossy().to_string())
-            .unwrap_or_else(|| friendly_path(&dest))
-    });
+    let name = add_cmd_opts.name.unwrap_or_else(|| default_name(&dest));
+    let source = if add_cmd_opts.project {
+        let project_file = match find_project_file() {
+            Some(project_file) => project_file,
+            None => env::current_dir()?.join(PROJECT_FILE),
+        };
+        Source::Project(project_file)
+    } else {
+        Source::Personal
+    };
+    let bookmark = Bookmark {
+        source: source.clone(),
+        ..Bookmark::new(name.clone(), dest.clone())
+    };
+
     let mut bookmarks = read_bookmarks().await?;
-    let existing = bookmarks.iter().enumerate().find(|(_, bm)| bm.name == name);
+    // Bookmarks of other sources with the same name stay. A personal one shadowed by a project
+    // bookmark isn't listed, so look for it in the personal file
+    let own = match source {
+        Source::Personal => read_personal_bookmarks().await?,
+        _ => bookmarks.clone(),
+    };
+    let existing = own.iter().find(|bm| bm.name == name && bm.source == source);
     let should_update = match existing {
-        None => {
-            bookmarks.push(Bookmark::new(name.clone(), dest.clone()).into());
-            true
+        Some(existing) if !add_cmd_opts.force => {
+            warn!(
+                "A bookmark with name {} already exists pointing at: {}",
+                existing.name,
+                friendly_path(&existing.dest)
+            );
+            info!("Consider using `--force` to replace the bookmark, or --name to give it a different name");
+            false
         }
-        Some((idx, existing)) => {
-            if add_cmd_opts.force {
-                bookmarks.remove(idx);
-                bookmarks.push(Bookmark::new(name.clone(), dest.clone()).into());
-                true
-            } else {
-                warn!(
-                    "A bookmark with name {} already exists pointing at: {}",
-                    existing.name,
-                    friendly_path(&existing.dest)
-                );
-                info!("Consider using `--force` to replace the bookmark, or --name to give it a different name");
-                false
-            }
+        _ => {
+            bookmarks.retain(|bm| bm.name != name || bm.source != source);
+            bookmarks.push(bookmark.into());
+            true
         }
     };
 
@@ -52,7 +64,26 @@ pub async fn add_cmd(add_cmd_opts: cli::AddCmd) -> Result<()> {
             friendly_path(&dest)
         );
         write_bookmarks(&bookmarks).await?;
+        let shadowing = bookmarks
+            .iter()
+            .find(|bm| bm.name == name && bm.source.priority() > source.priority());
+        if let Some(shadowing) = shadowing {
+            warn!(
+                "It's shadowed by the {} bookmark {}, which is listed instead",
+                shadowing.source.label().unwrap_or("personal"),
+                shadowing.name
+            );
+        }
     }
 
     Ok(())
 }
+
+/// Name of a bookmark when none is given: the name of the destination.
+pub fn default_name(dest: &Path) -> String {
+    // It's possible that the path is a root path (`/` or `C:\`) and file name N/A.
+    // In this case just use dest's friendly path
+    dest.file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_else(|| friendly_path(dest))
+}
@@ -1,20 +1,70 @@
 use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::path::{Path, PathBuf};
+use std::process;
 use std::sync::Arc;
 use tokio::fs::{self, OpenOptions};
+use tracing::warn;
 
-use crate::storage;
+use crate::{config, storage};
 use serde::{Deserialize, Serialize};
 
+// Found in the current directory or one of its parents
+pub const PROJECT_FILE: &str = ".shellmark.json";
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Bookmark {
     pub name: String,
     pub dest: PathBuf,
+    #[serde(skip)]
+    pub source: Source,
 }
 
 impl Bookmark {
     pub fn new(name: String, dest: PathBuf) -> Bookmark {
-        Bookmark { name, dest }
+        Bookmark {
+            name,
+            dest,
+            source: Source::Personal,
+        }
+    }
+}
+
+/// Where a bookmark comes from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Source {
+    /// The user's own bookmarks in the data directory
+    #[default]
+    Personal,
+    /// A file listed under `shared` in the config, never written to
+    Shared(PathBuf),
+    /// The `.shellmark.json` of the project the shell is in
+    Project(PathBuf),
+}
+
+impl Source {
+    pub fn is_writable(&self) -> bool {
+        !matches!(self, Source::Shared(_))
+    }
+
+    /// Of bookmarks with the same name, only the one of the source with the highest priority is
+    /// listed: the project's, then the personal one, then the shared ones.
+    pub fn priority(&self) -> u8 {
+        match self {
+            Source::Shared(_) => 0,
+            Source::Personal => 1,
+            Source::Project(_) => 2,
+        }
+    }
+
+    /// Short label for the browser, personal bookmarks have none.
+    pub fn label(&self) -> Option<&'static str> {
+        match self {
+            Source::Personal => None,
+            Source::Shared(_) => Some("shared"),
+            Source::Project(_) => Some("project"),
+        }
     }
 }
 
@@ -31,18 +81,142 @@ async fn get_or_create_bookmarks_file(data_dir: &Path) -> Result<PathBuf> {
     Ok(bookmarks_file)
 }
 
+/// The project file of the current directory, the closest one up the tree.
+pub fn find_project_file() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|file| file.is_file())
+}
+
+/// Bookmarks of all the sources: personal ones first, then the shared ones and the project's.
+///
+/// A name taken in several sources is listed once, see [`Source::priority`].
 pub async fn read_bookmarks() -> Result<Vec<Arc<Bookmark>>> {
-    let project_dir = storage::get_or_create_data_dir().await?;
-    let bookmarks_file = get_or_create_bookmarks_file(&project_dir).await?;
-    read_bookmarks_intern(&bookmarks_file)
-        .await
-        .map(|v| v.into_iter().map(Arc::new).collect())
+    let data_dir = storage::get_or_create_data_dir().await?;
+    let bookmarks_file = get_or_create_bookmarks_file(&data_dir).await?;
+    let mut bookmarks = read_bookmarks_intern(&bookmarks_file).await?;
+
+    // A shared file that's gone, e.g. of a repository that isn't cloned, shouldn't stop anything
+    for shared_file in config::get().shared.iter().filter(|file| file.exists()) {
+        match read_source(shared_file, Source::Shared(shared_file.clone())).await {
+            Ok(shared) => bookmarks.extend(shared),
+            Err(err) => warn!("Skipped shared bookmarks: {:#}", err),
+        }
+    }
+    if let Some(project_file) = find_project_file() {
+        match read_source(&project_file, Source::Project(project_file.clone())).await {
+            Ok(project) => bookmarks.extend(project),
+            Err(err) => warn!("Skipped project bookmarks: {:#}", err),
+        }
+    }
+
+    Ok(unshadowed(bookmarks).into_iter().map(Arc::new).collect())
+}
+
+/// The personal bookmarks only, including the ones a project bookmark shadows.
+pub async fn read_personal_bookmarks() -> Result<Vec<Arc<Bookmark>>> {
+    let data_dir = storage::get_or_create_data_dir().await?;
+    let bookmarks_file = get_or_create_bookmarks_file(&data_dir).await?;
+    let bookmarks = read_bookmarks_intern(&bookmarks_file).await?;
+    Ok(bookmarks.into_iter().map(Arc::new).collect())
+}
+
+// Drop the bookmarks whose name is taken by a source of higher priority, keeping the order.
+// Duplicates within a source stay, they are written back as they are
+fn unshadowed(bookmarks: Vec<Bookmark>) -> Vec<Bookmark> {
+    let mut priorities: HashMap<String, u8> = HashMap::new();
+    for bm in &bookmarks {
+        let priority = priorities.entry(bm.name.clone()).or_default();
+        *priority = (*priority).max(bm.source.priority());
+    }
+    bookmarks
+        .into_iter()
+        .filter(|bm| priorities[&bm.name] == bm.source.priority())
+        .collect()
 }
 
+/// Write the bookmarks back to their writable sources.
+///
+/// Every file is read and checked, and its new content put into a temporary file, before any
+/// of them is replaced. Each file is then replaced at once, so a reader never sees half of it.
 pub async fn write_bookmarks(bookmarks: &[Arc<Bookmark>]) -> Result<()> {
-    let project_dir = storage::get_or_create_data_dir().await?;
-    let bookmarks_file = get_or_create_bookmarks_file(&project_dir).await?;
-    write_bookmarks_intern(&bookmarks_file, bookmarks).await
+    let data_dir = storage::get_or_create_data_dir().await?;
+    let bookmarks_file = get_or_create_bookmarks_file(&data_dir).await?;
+    write_sources(&bookmarks_file, find_project_file(), bookmarks).await
+}
+
+async fn write_sources(
+    bookmarks_file: &Path,
+    current_project_file: Option<PathBuf>,
+    bookmarks: &[Arc<Bookmark>],
+) -> Result<()> {
+    let project_files = bookmarks.iter().filter_map(|bm| match &bm.source {
+        Source::Project(file) => Some(file.clone()),
+        _ => None,
+    });
+    let mut project_files: Vec<_> = project_files.chain(current_project_file.clone()).collect();
+    project_files.sort();
+    project_files.dedup();
+
+    let mut updates = vec![];
+    let mut shadowing = HashSet::new();
+    for project_file in project_files {
+        let source = Source::Project(project_file.clone());
+        let project: Vec<_> = bookmarks
+            .iter()
+            .filter(|bm| bm.source == source)
+            .map(Arc::as_ref)
+            .collect();
+        let written = match project_file.exists() {
+            true => read_bookmarks_intern(&project_file).await?,
+            false => vec![],
+        };
+        let current = resolve_dests(&project_file, written.clone(), &source);
+        if Some(&project_file) == current_project_file.as_ref() {
+            shadowing.extend(current.iter().map(|bm| bm.name.clone()));
+        }
+        // Project files are usually checked in, so leave them alone unless the bookmarks changed
+        if current.iter().ne(project.iter().copied()) {
+            let content = project_file_content(&project_file, &project, &written)?;
+            updates.push((project_file, content));
+        }
+    }
+
+    // The personal bookmarks a project bookmark shadows weren't listed, so they are kept, unless
+    // one with the same name took their place
+    let mut personal: Vec<_> = bookmarks
+        .iter()
+        .filter(|bm| bm.source == Source::Personal)
+        .map(|bm| bm.as_ref().clone())
+        .collect();
+    let names: HashSet<_> = personal.iter().map(|bm| bm.name.clone()).collect();
+    let shadowed = read_bookmarks_intern(bookmarks_file)
+        .await?
+        .into_iter()
+        .filter(|bm| shadowing.contains(&bm.name) && !names.contains(&bm.name));
+    personal.extend(shadowed);
+    updates.insert(0, (bookmarks_file.to_path_buf(), to_json(&personal)?));
+
+    let mut staged = vec![];
+    for (file, content) in updates {
+        match stage_file(&file, content).await {
+            Ok(tmp_file) => staged.push((tmp_file, file)),
+            Err(err) => {
+                remove_staged(&staged).await;
+                return Err(err);
+            }
+        }
+    }
+    for idx in 0..staged.len() {
+        let (tmp_file, file) = &staged[idx];
+        if let Err(err) = fs::rename(tmp_file, file).await {
+            remove_staged(&staged[idx..]).await;
+            return Err(err)
+                .with_context(|| format!("Couldn't write bookmarks file: {}", file.display()));
+        }
+    }
+    Ok(())
 }
 
 async fn read_bookmarks_intern(bookmarks_file: &Path) -> Result<Vec<Bookmark>> {
@@ -53,13 +227,216 @@ async fn read_bookmarks_intern(bookmarks_file: &Path) -> Result<Vec<Bookmark>> {
     if content.trim().is_empty() {
         Ok(Vec::new())
     } else {
-        serde_json::from_str(&content).context("Couldn't parse bookmarks JSON")
+        serde_json::from_str(&content).with_context(|| {
+            format!(
+                "Couldn't parse bookmarks JSON: {}",
+                bookmarks_file.display()
+            )
+        })
+    }
+}
+
+// Destinations of shared and project files can start with `~` or be relative to the file, so
+// the same file works for everyone
+async fn read_source(bookmarks_file: &Path, source: Source) -> Result<Vec<Bookmark>> {
+    let bookmarks = read_bookmarks_intern(bookmarks_file).await?;
+    Ok(resolve_dests(bookmarks_file, bookmarks, &source))
+}
+
+fn resolve_dests(
+    bookmarks_file: &Path,
+    bookmarks: Vec<Bookmark>,
+    source: &Source,
+) -> Vec<Bookmark> {
+    let base_dir = bookmarks_file.parent().unwrap_or(Path::new(""));
+    bookmarks
+        .into_iter()
+        .map(|bm| Bookmark {
+            dest: resolve_dest(base_dir, &bm.dest),
+            source: source.clone(),
+            ..bm
+        })
+        .collect()
+}
+
+fn resolve_dest(base_dir: &Path, dest: &Path) -> PathBuf {
+    // Without the `.` of the project directory itself
+    base_dir
+        .join(storage::expand_home(dest))
+        .components()
+        .collect()
+}
+
+// The destinations are written the way they were if they didn't change, and new ones relative to
+// the project or the home directory when they are inside them
+fn project_file_content(
+    project_file: &Path,
+    bookmarks: &[&Bookmark],
+    written: &[Bookmark],
+) -> Result<String> {
+    let base_dir = project_file.parent().unwrap_or(Path::new(""));
+    let bookmarks: Vec<_> = bookmarks
+        .iter()
+        .map(|bm| {
+            let unchanged = written
+                .iter()
+                .find(|w| w.name == bm.name && resolve_dest(base_dir, &w.dest) == bm.dest);
+            let dest = match (unchanged, bm.dest.strip_prefix(base_dir)) {
+                (Some(w), _) => w.dest.clone(),
+                (None, Ok(rel)) if rel.as_os_str().is_empty() => PathBuf::from("."),
+                (None, Ok(rel)) => rel.to_path_buf(),
+                (None, Err(_)) => match bm.dest.strip_prefix(storage::home_dir()) {
+                    Ok(rel) => Path::new("~").join(rel),
+                    Err(_) => bm.dest.clone(),
+                },
+            };
+            Bookmark::new(bm.name.clone(), dest)
+        })
+        .collect();
+    to_json(&bookmarks)
+}
+
+async fn remove_staged(staged: &[(PathBuf, PathBuf)]) {
+    for (tmp_file, _) in staged {
+        let _ = fs::remove_file(tmp_file).await;
+    }
+}
+
+fn to_json(bookmarks: &[Bookmark]) -> Result<String> {
+    serde_json::to_string_pretty(bookmarks).context("Couldn't serialize bookmarks to JSON")
+}
+
+// Put the content next to the file to replace it with a rename, which readers never see half
+// done. A temporary file that couldn't be written is removed, it may be in a git checkout
+async fn stage_file(bookmarks_file: &Path, content: String) -> Result<PathBuf> {
+    let tmp_file = bookmarks_file.with_extension(format!("json.{}", process::id()));
+    match fs::write(&tmp_file, content).await {
+        Ok(()) => Ok(tmp_file),
+        Err(err) => {
+            let _ = fs::remove_file(&tmp_file).await;
+            Err(err).with_context(|| {
+                format!(
+                    "Couldn't write bookmarks file: {}",
+                    bookmarks_file.display()
+                )
+            })
+        }
     }
 }
 
-async fn write_bookmarks_intern(bookmarks_file: &Path, bookmarks: &[Arc<Bookmark>]) -> Result<()> {
-    let content =
-        serde_json::to_string_pretty(&bookmarks.iter().map(Arc::as_ref).collect::<Vec<_>>())
-            .context("Couldn't serialize bookmarks to JSON")?;
-    Ok(fs::write(bookmarks_file, content).await?)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(name: &str, source: &Source) -> Bookmark {
+        Bookmark {
+            source: source.clone(),
+            ..Bookmark::new(name.to_string(), PathBuf::from("/"))
+        }
+    }
+
+    #[test]
+    fn names_are_taken_by_the_source_of_the_highest_priority() {
+        let shared = Source::Shared(PathBuf::from("/team/bookmarks.json"));
+        let project = Source::Project(PathBuf::from("/src/app/.shellmark.json"));
+        let bookmarks = vec![
+            bookmark("docs", &Source::Personal),
+            bookmark("app", &Source::Personal),
+            bookmark("notes", &Source::Personal),
+            bookmark("notes", &Source::Personal),
+            bookmark("docs", &shared),
+            bookmark("wiki", &shared),
+            bookmark("app", &shared),
+            bookmark("app", &project),
+        ];
+        let listed: Vec<_> = unshadowed(bookmarks)
+            .into_iter()
+            .map(|bm| (bm.name, bm.source.label()))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("docs".to_string(), None),
+                ("notes".to_string(), None),
+                ("notes".to_string(), None),
+                ("wiki".to_string(), Some("shared")),
+                ("app".to_string(), Some("project")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn project_files_are_written_back_as_they_were_spelled() {
+        let root = env::temp_dir().join(format!("shellmark-write-{}", process::id()));
+        let app = root.join("app");
+        std::fs::create_dir_all(&app).unwrap();
+        let personal_file = root.join("bookmarks.json");
+        let project_file = app.join(PROJECT_FILE);
+        std::fs::write(
+            &personal_file,
+            r#"[{"name": "app", "dest": "/personal/app"}, {"name": "other", "dest": "/other"}]"#,
+        )
+        .unwrap();
+        std::fs::write(
+            &project_file,
+            r#"[
+                {"name": "team-docs", "dest": "~/docs"},
+                {"name": "src", "dest": "src"},
+                {"name": "app", "dest": "."}
+            ]"#,
+        )
+        .unwrap();
+
+        // What the browser lists, with two bookmarks added to the project
+        let source = Source::Project(project_file.clone());
+        let mut bookmarks = read_bookmarks_intern(&personal_file).await.unwrap();
+        bookmarks.extend(read_source(&project_file, source.clone()).await.unwrap());
+        let mut bookmarks = unshadowed(bookmarks);
+        for (name, dest) in [
+            ("sub", app.join("sub")),
+            ("notes", storage::home_dir().join("notes")),
+        ] {
+            bookmarks.push(Bookmark {
+                source: source.clone(),
+                ..Bookmark::new(name.to_string(), dest)
+            });
+        }
+        let bookmarks: Vec<_> = bookmarks.into_iter().map(Arc::new).collect();
+        write_sources(&personal_file, Some(project_file.clone()), &bookmarks)
+            .await
+            .unwrap();
+
+        let written = |file: &Path| -> Vec<(String, String)> {
+            let content = std::fs::read_to_string(file).unwrap();
+            let bookmarks: Vec<Bookmark> = serde_json::from_str(&content).unwrap();
+            bookmarks
+                .into_iter()
+                .map(|bm| (bm.name, bm.dest.to_string_lossy().into_owned()))
+                .collect()
+        };
+        let pair = |name: &str, dest: &str| (name.to_string(), dest.to_string());
+        assert_eq!(
+            written(&project_file),
+            [
+                pair("team-docs", "~/docs"),
+                pair("src", "src"),
+                pair("app", "."),
+                pair("sub", "sub"),
+                pair("notes", &Path::new("~").join("notes").to_string_lossy()),
+            ]
+        );
+        // The personal bookmark the project's shadows isn't lost
+        assert_eq!(
+            written(&personal_file),
+            [pair("other", "/other"), pair("app", "/personal/app")]
+        );
+        let mut files: Vec<_> = std::fs::read_dir(&app)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        files.sort();
+        assert_eq!(files, [PROJECT_FILE]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
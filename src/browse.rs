@@ -1,5 +1,10 @@
 use std::{
-    convert::From, fmt::Display, iter::FromIterator, ops::Range, path::PathBuf, sync::Arc,
+    convert::From,
+    ffi::OsStr,
+    fmt::Display,
+    iter::FromIterator,
+    path::{Path, PathBuf},
+    sync::Arc,
     time::Duration,
 };
 
@@ -9,14 +14,17 @@ use derivative::*;
 
 use crossterm::event::Event;
 
-use fuzzy_matcher::skim::SkimMatcherV2;
 use tokio::{fs, time::Instant};
 
 use crate::{
     bookmarks::{write_bookmarks, Bookmark},
-    search,
-    shell::{self, is_editor_set},
+    config::{self, DefaultAction},
+    keys::KeySpec,
+    search::{self, Matcher},
+    shell::{self, editor_command, is_editor_set},
     storage::simplify_path,
+    suggest::{find_suggestions, Suggestion},
+    visits::Visits,
 };
 
 mod cmd;
@@ -24,9 +32,8 @@ mod ui;
 
 pub use cmd::browse_cmd;
 
-// Not strictly needed now as there are no background activities not related to terminal events
-// But let's keep just in case
-const REFRESH_RATE_MS: Duration = Duration::from_millis(1000);
+// Ticks are used to expire key sequences that weren't completed in time
+const REFRESH_RATE_MS: Duration = Duration::from_millis(250);
 
 #[derive(Debug, Clone, Copy)]
 struct Tick;
@@ -56,6 +63,12 @@ pub struct Input {
 }
 
 impl Input {
+    pub fn from_query(query: &str) -> Self {
+        let input: Vec<char> = query.chars().collect();
+        let cursor = input.len() as u16;
+        Input { input, cursor }
+    }
+
     pub fn insert_char(&self, c: char) -> Self {
         let mut new_state = self.clone();
         new_state.input.insert(new_state.cursor as usize, c);
@@ -88,6 +101,8 @@ pub struct Selection {
     pub candidates: Vec<usize>,
     // idx into selection
     pub selected: Option<usize>,
+    // idx into selection of the first visible row
+    pub offset: usize,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -105,21 +120,21 @@ impl MoveDirection {
     }
 }
 
+// Number of rows to keep visible above and below the highlighted one
+const SCROLL_CONTEXT: usize = 2;
+
 impl Selection {
     pub fn from_bookmarks_with_selected(
         bookmarks: &[Arc<Bookmark>],
+        visits: &Visits,
         selected: Option<usize>,
     ) -> Self {
-        let candidates = Range {
-            start: 0,
-            end: bookmarks.len(),
-        }
-        .collect();
+        let candidates = search::sort_bookmarks(bookmarks, config::get().sort, visits);
         Self::from_candidates_with_selected(candidates, selected)
     }
 
-    pub fn from_bookmarks(bookmarks: &[Arc<Bookmark>]) -> Self {
-        Self::from_bookmarks_with_selected(bookmarks, None)
+    pub fn from_bookmarks(bookmarks: &[Arc<Bookmark>], visits: &Visits) -> Self {
+        Self::from_bookmarks_with_selected(bookmarks, visits, None)
     }
 
     pub fn from_candidates_with_selected(candidates: Vec<usize>, selected: Option<usize>) -> Self {
@@ -133,32 +148,207 @@ impl Selection {
         Self {
             candidates,
             selected,
+            offset: 0,
         }
     }
 
-    pub fn move_highlight(&self, direction: &MoveDirection) -> Self {
+    pub fn move_highlight(&self, direction: &MoveDirection, cycle: bool) -> Self {
         if self.candidates.is_empty() {
             return self.clone();
         }
         match self.selected {
-            None => Selection {
-                selected: Some(0),
-                ..self.clone()
-            },
+            None => self.move_to(0),
             Some(line) => {
-                let increment = direction.increment();
-                let new_line = (line as isize + increment as isize)
-                    .max(0)
-                    .min(self.candidates.len() as isize - 1)
-                    as usize;
-
-                Selection {
-                    selected: Some(new_line),
-                    ..self.clone()
+                let last = self.candidates.len() - 1;
+                match direction {
+                    MoveDirection::Down if cycle && line == last => self.move_to(0),
+                    MoveDirection::Up if cycle && line == 0 => self.move_to(last),
+                    _ => self.move_by(direction.increment() as isize),
+                }
+            }
+        }
+    }
+
+    pub fn move_by(&self, delta: isize) -> Self {
+        if self.candidates.is_empty() {
+            return self.clone();
+        }
+        let line = self.selected.unwrap_or(0) as isize + delta;
+        self.move_to(line.max(0) as usize)
+    }
+
+    pub fn move_to(&self, line: usize) -> Self {
+        if self.candidates.is_empty() {
+            return self.clone();
+        }
+        Selection {
+            selected: Some(line.min(self.candidates.len() - 1)),
+            ..self.clone()
+        }
+    }
+
+    /// Adjust the offset so that the highlighted row is visible in a list of the given height,
+    /// together with a few rows of context around it.
+    pub fn scroll(&self, height: usize) -> Self {
+        if height == 0 {
+            return self.clone();
+        }
+        let max_offset = self.candidates.len().saturating_sub(height);
+        let mut offset = self.offset.min(max_offset);
+        if let Some(line) = self.selected {
+            let context = SCROLL_CONTEXT.min((height - 1) / 2);
+            if line < offset + context {
+                offset = line.saturating_sub(context);
+            } else if line + context >= offset + height {
+                offset = (line + context + 1 - height).min(max_offset);
+            }
+        }
+        Selection {
+            offset,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct HelpState {
+    // filter for the key bindings
+    pub query: Input,
+    // idx of the first visible line
+    pub scroll: usize,
+}
+
+/// Actions applicable to the selected bookmark, shown in a popup.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ActionMenu {
+    pub items: Vec<MenuItem>,
+    pub selected: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MenuItem {
+    pub label: String,
+    pub cmd: Command,
+}
+
+impl MenuItem {
+    fn new(label: &str, cmd: Command) -> Self {
+        MenuItem {
+            label: label.to_string(),
+            cmd,
+        }
+    }
+}
+
+/// Often visited directories that aren't bookmarked, shown in a popup.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SuggestionList {
+    pub items: Vec<Suggestion>,
+    pub selected: usize,
+}
+
+// Enough to fill the popup without scrolling
+const SUGGESTION_LIMIT: usize = 10;
+
+impl SuggestionList {
+    async fn new(bookmarks: &[Arc<Bookmark>], visits: &Visits, selected: usize) -> Self {
+        let items = find_suggestions(bookmarks, visits, SUGGESTION_LIMIT).await;
+        let selected = selected.min(items.len().saturating_sub(1));
+        SuggestionList { items, selected }
+    }
+
+    fn move_highlight(&self, direction: &MoveDirection) -> Self {
+        let len = self.items.len().max(1) as isize;
+        let selected = (self.selected as isize + direction.increment() as isize).rem_euclid(len);
+        SuggestionList {
+            selected: selected as usize,
+            ..self.clone()
+        }
+    }
+}
+
+impl ActionMenu {
+    async fn for_bookmark(bm: &Bookmark) -> Self {
+        let mut items = vec![];
+        let meta = fs::metadata(&bm.dest).await.ok();
+        match &meta {
+            Some(meta) if meta.is_dir() => {
+                items.push(MenuItem::new("Jump to the directory", Command::EnterSelDir));
+                if is_editor_set() {
+                    items.push(MenuItem::new("Open in editor", Command::OpenSelInEditor));
+                }
+                items.push(MenuItem::new(
+                    "Open in file manager",
+                    Command::OpenSelWithSystem,
+                ));
+                items.push(MenuItem::new(
+                    "Jump to the parent directory",
+                    Command::EnterParentDir,
+                ));
+            }
+            Some(_) => {
+                if is_editor_set() {
+                    items.push(MenuItem::new("Open in editor", Command::OpenSelInEditor));
                 }
+                items.push(MenuItem::new(
+                    "Open with default application",
+                    Command::OpenSelWithSystem,
+                ));
+                items.push(MenuItem::new(
+                    "Jump to the containing directory",
+                    Command::EnterSelDir,
+                ));
+                items.push(MenuItem::new(
+                    "Show in file manager",
+                    Command::OpenSelDirWithSystem,
+                ));
+            }
+            // The destination is gone, so there's nothing to do but to delete the bookmark
+            None => {}
+        }
+        items.push(MenuItem::new("Copy path", Command::CopySelPath));
+        for (idx, action) in config::get().actions.iter().enumerate() {
+            if action.applies_to.matches(meta.as_ref()) {
+                items.push(MenuItem::new(action.desc(), Command::RunCustomAction(idx)));
             }
         }
+        if bm.source.is_writable() {
+            items.push(MenuItem::new(
+                "Rename bookmark",
+                Command::EnterMode(Mode::Rename),
+            ));
+            items.push(MenuItem::new(
+                "Delete bookmark",
+                Command::EnterMode(Mode::PendingDelete),
+            ));
+        }
+
+        ActionMenu { items, selected: 0 }
     }
+
+    fn move_highlight(&self, direction: &MoveDirection) -> Self {
+        let len = self.items.len().max(1) as isize;
+        let selected = (self.selected as isize + direction.increment() as isize).rem_euclid(len);
+        ActionMenu {
+            selected: selected as usize,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryNav {
+    // idx into history of the entry currently shown in the input
+    pub pos: usize,
+    // the query typed before navigating the history
+    pub pattern: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingKeys {
+    // keys of a sequence typed so far
+    pub keys: Vec<KeySpec>,
+    pub since: Instant,
 }
 
 #[derive(Derivative)]
@@ -166,102 +356,263 @@ impl Selection {
 pub struct BrowseState {
     pub bookmarks: Vec<Arc<Bookmark>>,
     #[derivative(Debug = "ignore", PartialEq = "ignore")]
-    pub matcher: Arc<SkimMatcherV2>,
+    pub matcher: Arc<Matcher>,
+    // visit statistics used to rank bookmarks
+    #[derivative(Debug = "ignore", PartialEq = "ignore")]
+    pub visits: Arc<Visits>,
     pub input: Input,
+    // past queries, oldest first
+    #[derivative(Debug = "ignore")]
+    pub history: Vec<String>,
+    pub history_nav: Option<HistoryNav>,
     pub selection: Selection,
+    // number of rows that fit into the bookmark list
+    pub list_height: usize,
+    // wrap around when moving past the first or the last row
+    pub cycle: bool,
+    // the default action inserts the path into the command line
+    pub insert_path: bool,
     pub mode: Mode,
+    // mode to go back to when leaving a dialog or the help
+    pub base_mode: Mode,
+    pub help: HelpState,
+    pub menu: ActionMenu,
+    pub suggestions: SuggestionList,
+    // new name of the selected bookmark
+    pub rename: Input,
+    pub pending: Option<PendingKeys>,
     pub last_refresh_at: Option<Instant>,
+    // time and line of the last mouse click, used to detect double clicks
+    #[derivative(PartialEq = "ignore")]
+    pub last_click: Option<(Instant, usize)>,
 }
 
+// The state is passed along once per event, so there's no point in boxing it
+#[allow(clippy::large_enum_variant)]
 pub enum HandleResult {
     Continue(BrowseState),
     Terminate(Option<Action>),
 }
 
 impl BrowseState {
-    pub fn new(bookmarks: Vec<Arc<Bookmark>>, matcher: Arc<SkimMatcherV2>) -> BrowseState {
+    pub fn new(
+        bookmarks: Vec<Arc<Bookmark>>,
+        matcher: Arc<Matcher>,
+        visits: Arc<Visits>,
+        history: Vec<String>,
+        cycle: bool,
+        insert_path: bool,
+    ) -> BrowseState {
         let input = Input::default();
-        let selection = Selection::from_bookmarks(&bookmarks);
+        let selection = Selection::from_bookmarks(&bookmarks, &visits);
         BrowseState {
             bookmarks,
             matcher,
+            visits,
             input,
+            history,
+            history_nav: None,
             selection,
+            list_height: 0,
+            cycle,
+            insert_path,
             mode: Mode::Normal,
+            base_mode: Mode::Normal,
+            help: HelpState::default(),
+            menu: ActionMenu::default(),
+            suggestions: SuggestionList::default(),
+            rename: Input::default(),
+            pending: None,
             last_refresh_at: None,
+            last_click: None,
         }
     }
 
     pub async fn handle_command(&self, cmd: &Command) -> Result<HandleResult> {
         match cmd {
             Command::ExitApp => Ok(HandleResult::Terminate(None)),
-            Command::DefaultAction => {
-                if let Some(bm) = self.selected_bookmark() {
-                    let meta = fs::metadata(&bm.dest).await?;
-                    if meta.is_file() {
-                        if is_editor_set() {
+            Command::DefaultAction if self.insert_path => Ok(self.insert_sel_path()),
+            Command::InsertSelPath => Ok(self.insert_sel_path()),
+            Command::DefaultAction => match config::get().default_action {
+                DefaultAction::Cd => self.enter_sel_dir().await,
+                DefaultAction::Edit => Ok(self.open_sel_in_editor()),
+                DefaultAction::Auto => {
+                    if let Some(bm) = self.selected_bookmark() {
+                        let meta = fs::metadata(&bm.dest).await?;
+                        if meta.is_file() && is_editor_set() {
                             Ok(HandleResult::Terminate(Some(Action::OpenInEditorAction {
                                 dest: bm.dest.clone(),
                             })))
                         } else {
-                            let dest = bm
-                                .dest
-                                .parent()
-                                .expect("File doesn't have a parent dir")
-                                .to_path_buf();
-                            Ok(HandleResult::Terminate(Some(Action::ChangeDirAction {
-                                dest,
-                            })))
+                            self.enter_sel_dir().await
                         }
                     } else {
-                        Ok(HandleResult::Terminate(Some(Action::ChangeDirAction {
-                            dest: bm.dest.clone(),
-                        })))
+                        Ok(HandleResult::Continue(self.clone()))
                     }
-                } else {
-                    Ok(HandleResult::Continue(self.clone()))
                 }
-            }
-            Command::OpenSelInEditor => {
-                if let Some(bm) = self.selected_bookmark() {
-                    Ok(HandleResult::Terminate(Some(Action::OpenInEditorAction {
+            },
+            Command::OpenSelInEditor => Ok(self.open_sel_in_editor()),
+            Command::EnterSelDir => self.enter_sel_dir().await,
+            Command::OpenSelWithSystem => match self.selected_bookmark() {
+                Some(bm) => Ok(HandleResult::Terminate(Some(
+                    Action::OpenWithSystemAction {
                         dest: bm.dest.clone(),
-                    })))
-                } else {
-                    Ok(HandleResult::Continue(self.clone()))
-                }
-            }
-            Command::EnterSelDir => {
-                if let Some(bm) = self.selected_bookmark() {
-                    let meta = fs::metadata(&bm.dest).await?;
-                    let dest = if meta.is_file() {
-                        bm.dest
-                            .parent()
-                            .expect("File doesn't have a parent dir")
-                            .to_path_buf()
-                    } else {
-                        bm.dest.clone()
-                    };
-
+                    },
+                ))),
+                None => Ok(HandleResult::Continue(self.clone())),
+            },
+            Command::CopySelPath => match self.selected_bookmark() {
+                Some(bm) => Ok(HandleResult::Terminate(Some(Action::CopyPathAction {
+                    dest: bm.dest.clone(),
+                }))),
+                None => Ok(HandleResult::Continue(self.clone())),
+            },
+            Command::OpenSelDirWithSystem => match self.sel_dir().await? {
+                Some(dest) => Ok(HandleResult::Terminate(Some(
+                    Action::OpenWithSystemAction { dest },
+                ))),
+                None => Ok(HandleResult::Continue(self.clone())),
+            },
+            Command::RunCustomAction(idx) => self.run_custom_action(*idx).await,
+            Command::EnterParentDir => match self.selected_bookmark() {
+                Some(bm) => {
+                    let dest = bm.dest.parent().unwrap_or(&bm.dest).to_path_buf();
                     Ok(HandleResult::Terminate(Some(Action::ChangeDirAction {
                         dest,
                     })))
-                } else {
-                    Ok(HandleResult::Continue(self.clone()))
                 }
+                None => Ok(HandleResult::Continue(self.clone())),
+            },
+            Command::OpenActionMenu => match self.selected_bookmark() {
+                Some(bm) => {
+                    let mut new_state = self.clone();
+                    new_state.menu = ActionMenu::for_bookmark(&bm).await;
+                    new_state.enter_mode(Mode::ActionMenu);
+                    Ok(HandleResult::Continue(new_state))
+                }
+                None => Ok(HandleResult::Continue(self.clone())),
+            },
+            Command::RunMenuItem => match self.menu.items.get(self.menu.selected) {
+                Some(item) => {
+                    let mut new_state = self.clone();
+                    new_state.enter_mode(self.base_mode);
+                    Box::pin(new_state.handle_command(&item.cmd)).await
+                }
+                None => Ok(HandleResult::Continue(self.clone())),
+            },
+            Command::MoveSel(direction) if self.mode == Mode::ActionMenu => {
+                Ok(HandleResult::Continue(BrowseState {
+                    menu: self.menu.move_highlight(direction),
+                    ..self.clone()
+                }))
+            }
+            Command::OpenSuggestions => {
+                let mut new_state = self.clone();
+                new_state.suggestions = SuggestionList::new(&self.bookmarks, &self.visits, 0).await;
+                new_state.enter_mode(Mode::Suggestions);
+                Ok(HandleResult::Continue(new_state))
+            }
+            Command::BookmarkSuggestion => {
+                match self.suggestions.items.get(self.suggestions.selected) {
+                    Some(suggestion) if !suggestion.name_taken => {
+                        let mut new_state = self.clone();
+                        new_state.bookmarks.push(Arc::new(Bookmark::new(
+                            suggestion.name.clone(),
+                            suggestion.dir.clone(),
+                        )));
+                        write_bookmarks(&new_state.bookmarks).await?;
+                        new_state.update_selection();
+                        new_state.suggestions = SuggestionList::new(
+                            &new_state.bookmarks,
+                            &self.visits,
+                            self.suggestions.selected,
+                        )
+                        .await;
+                        Ok(HandleResult::Continue(new_state))
+                    }
+                    _ => Ok(HandleResult::Continue(self.clone())),
+                }
+            }
+            Command::MoveSel(direction) if self.mode == Mode::Suggestions => {
+                Ok(HandleResult::Continue(BrowseState {
+                    suggestions: self.suggestions.move_highlight(direction),
+                    ..self.clone()
+                }))
             }
             Command::DelSelBookmark => {
                 let mut new_state = self.clone();
-                if let Some(bm) = new_state.selected_bookmark() {
+                // Shared bookmarks are read-only
+                if let Some(bm) = new_state
+                    .selected_bookmark()
+                    .filter(|bm| bm.source.is_writable())
+                {
                     new_state.remove_bookmark(&bm);
                     write_bookmarks(&new_state.bookmarks).await?;
                 }
-                new_state.enter_mode(Mode::Normal);
+                new_state.enter_mode(new_state.base_mode);
+                Ok(HandleResult::Continue(new_state))
+            }
+            Command::RenameSelBookmark => {
+                let mut new_state = self.clone();
+                if let Some(bm) = self.selected_bookmark() {
+                    let name = self.rename.to_string();
+                    if self.is_name_taken(&bm, &name) {
+                        return Ok(HandleResult::Continue(new_state));
+                    }
+                    if !name.is_empty() && name != bm.name {
+                        let renamed = Arc::new(Bookmark {
+                            name,
+                            ..bm.as_ref().clone()
+                        });
+                        for b in new_state.bookmarks.iter_mut() {
+                            if *b == bm {
+                                *b = renamed.clone();
+                            }
+                        }
+                        write_bookmarks(&new_state.bookmarks).await?;
+                        new_state.update_selection();
+                    }
+                }
+                new_state.enter_mode(new_state.base_mode);
+                Ok(HandleResult::Continue(new_state))
+            }
+            Command::InsertChar(c) if self.mode == Mode::Rename => {
+                Ok(HandleResult::Continue(BrowseState {
+                    rename: self.rename.insert_char(*c),
+                    ..self.clone()
+                }))
+            }
+            Command::DeleteCharBack if self.mode == Mode::Rename => {
+                Ok(HandleResult::Continue(BrowseState {
+                    rename: self.rename.delete_char_backwards(),
+                    ..self.clone()
+                }))
+            }
+            Command::ClearInput if self.mode == Mode::Rename => {
+                Ok(HandleResult::Continue(BrowseState {
+                    rename: Input::default(),
+                    ..self.clone()
+                }))
+            }
+            Command::InsertChar(c) if self.mode == Mode::Help => Ok(HandleResult::Continue(
+                self.edit_help_query(self.help.query.insert_char(*c)),
+            )),
+            Command::DeleteCharBack if self.mode == Mode::Help => Ok(HandleResult::Continue(
+                self.edit_help_query(self.help.query.delete_char_backwards()),
+            )),
+            Command::ClearInput if self.mode == Mode::Help => Ok(HandleResult::Continue(
+                self.edit_help_query(Input::default()),
+            )),
+            Command::ScrollHelp(direction) => {
+                let mut new_state = self.clone();
+                new_state.help.scroll =
+                    (self.help.scroll as isize + direction.increment() as isize).max(0) as usize;
                 Ok(HandleResult::Continue(new_state))
             }
             Command::InsertChar(c) => {
                 let mut new_state = BrowseState {
                     input: self.input.insert_char(*c),
+                    history_nav: None,
                     ..self.clone()
                 };
                 new_state.update_selection();
@@ -270,6 +621,7 @@ impl BrowseState {
             Command::DeleteCharBack => {
                 let mut new_state = BrowseState {
                     input: self.input.delete_char_backwards(),
+                    history_nav: None,
                     ..self.clone()
                 };
                 new_state.update_selection();
@@ -278,23 +630,184 @@ impl BrowseState {
             Command::ClearInput => {
                 let mut new_state = BrowseState {
                     input: Input::default(),
+                    history_nav: None,
                     ..self.clone()
                 };
                 new_state.update_selection();
                 Ok(HandleResult::Continue(new_state))
             }
             Command::MoveSel(direction) => {
-                let new_selection = self.selection.move_highlight(direction);
+                let new_selection = self.selection.move_highlight(direction, self.cycle);
                 Ok(HandleResult::Continue(BrowseState {
                     selection: new_selection,
                     ..self.clone()
                 }))
             }
+            Command::MovePage(direction) => {
+                let delta = self.list_height.max(1) as isize * direction.increment() as isize;
+                Ok(HandleResult::Continue(BrowseState {
+                    selection: self.selection.move_by(delta),
+                    ..self.clone()
+                }))
+            }
+            Command::MoveHalfPage(direction) => {
+                let delta = (self.list_height / 2).max(1) as isize * direction.increment() as isize;
+                Ok(HandleResult::Continue(BrowseState {
+                    selection: self.selection.move_by(delta),
+                    ..self.clone()
+                }))
+            }
+            Command::SelectLine(line) => Ok(HandleResult::Continue(BrowseState {
+                selection: self.selection.move_to(*line),
+                ..self.clone()
+            })),
+            Command::MoveFirst => Ok(HandleResult::Continue(BrowseState {
+                selection: self.selection.move_to(0),
+                ..self.clone()
+            })),
+            Command::MoveLast => Ok(HandleResult::Continue(BrowseState {
+                selection: self.selection.move_to(usize::MAX),
+                ..self.clone()
+            })),
             Command::EnterMode(mode) => {
                 let mut new_state = self.clone();
                 new_state.enter_mode(*mode);
                 Ok(HandleResult::Continue(new_state))
             }
+            Command::LeaveMode => {
+                let mut new_state = self.clone();
+                new_state.enter_mode(self.base_mode);
+                Ok(HandleResult::Continue(new_state))
+            }
+            Command::HistoryPrev => {
+                let pos = match &self.history_nav {
+                    None => self.history.len().checked_sub(1),
+                    Some(nav) => Some(nav.pos.saturating_sub(1)),
+                };
+                Ok(HandleResult::Continue(self.recall_history(pos)))
+            }
+            Command::HistoryNext => match &self.history_nav {
+                None => Ok(HandleResult::Continue(self.clone())),
+                Some(nav) if nav.pos + 1 < self.history.len() => Ok(HandleResult::Continue(
+                    self.recall_history(Some(nav.pos + 1)),
+                )),
+                Some(nav) => {
+                    // Went past the most recent entry, so restore what the user has typed
+                    let mut new_state = BrowseState {
+                        input: Input::from_query(&nav.pattern),
+                        history_nav: None,
+                        ..self.clone()
+                    };
+                    new_state.update_selection();
+                    Ok(HandleResult::Continue(new_state))
+                }
+            },
+            Command::HistorySearch => {
+                let (pattern, end) = match &self.history_nav {
+                    None => (self.input.to_string(), self.history.len()),
+                    Some(nav) => (nav.pattern.clone(), nav.pos),
+                };
+                let pos = self.history[..end]
+                    .iter()
+                    .rposition(|query| self.matcher.score(query, &pattern).is_some());
+                match pos {
+                    Some(_) => Ok(HandleResult::Continue(self.recall_history(pos))),
+                    None => Ok(HandleResult::Continue(self.clone())),
+                }
+            }
+        }
+    }
+
+    async fn enter_sel_dir(&self) -> Result<HandleResult> {
+        if let Some(dest) = self.sel_dir().await? {
+            Ok(HandleResult::Terminate(Some(Action::ChangeDirAction {
+                dest,
+            })))
+        } else {
+            Ok(HandleResult::Continue(self.clone()))
+        }
+    }
+
+    // The selected directory, or the directory of the selected file
+    async fn sel_dir(&self) -> Result<Option<PathBuf>> {
+        if let Some(bm) = self.selected_bookmark() {
+            let meta = fs::metadata(&bm.dest).await?;
+            let dest = if meta.is_file() {
+                bm.dest
+                    .parent()
+                    .expect("File doesn't have a parent dir")
+                    .to_path_buf()
+            } else {
+                bm.dest.clone()
+            };
+            Ok(Some(dest))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn run_custom_action(&self, idx: usize) -> Result<HandleResult> {
+        let action = &config::get().actions[idx];
+        if let Some(bm) = self.selected_bookmark() {
+            let meta = fs::metadata(&bm.dest).await.ok();
+            if action.applies_to.matches(meta.as_ref()) {
+                let dir = match meta {
+                    Some(meta) if meta.is_file() => bm.dest.parent().unwrap_or(&bm.dest),
+                    _ => &bm.dest,
+                };
+                return Ok(HandleResult::Terminate(Some(Action::CustomAction {
+                    command: action.command.clone(),
+                    name: bm.name.clone(),
+                    dest: bm.dest.clone(),
+                    dir: dir.to_path_buf(),
+                })));
+            }
+        }
+        Ok(HandleResult::Continue(self.clone()))
+    }
+
+    fn insert_sel_path(&self) -> HandleResult {
+        match self.selected_bookmark() {
+            Some(bm) => HandleResult::Terminate(Some(Action::InsertPathAction {
+                dest: bm.dest.clone(),
+            })),
+            None => HandleResult::Continue(self.clone()),
+        }
+    }
+
+    fn open_sel_in_editor(&self) -> HandleResult {
+        if let Some(bm) = self.selected_bookmark() {
+            HandleResult::Terminate(Some(Action::OpenInEditorAction {
+                dest: bm.dest.clone(),
+            }))
+        } else {
+            HandleResult::Continue(self.clone())
+        }
+    }
+
+    fn recall_history(&self, pos: Option<usize>) -> BrowseState {
+        let pos = match pos {
+            Some(pos) => pos,
+            None => return self.clone(),
+        };
+        let pattern = match &self.history_nav {
+            None => self.input.to_string(),
+            Some(nav) => nav.pattern.clone(),
+        };
+        let mut new_state = BrowseState {
+            input: Input::from_query(&self.history[pos]),
+            history_nav: Some(HistoryNav { pos, pattern }),
+            ..self.clone()
+        };
+        new_state.update_selection();
+        new_state
+    }
+
+    /// Keys of the sequence typed so far, unless it wasn't continued within the timeout.
+    pub fn pending_keys(&self, timeout: Duration) -> &[KeySpec] {
+        match &self.pending {
+            Some(pending) if pending.since.elapsed() < timeout => &pending.keys,
+            _ => &[],
         }
     }
 
@@ -313,53 +826,477 @@ impl BrowseState {
     pub fn update_selection(&mut self) {
         let input = self.input.to_string();
         let selection = if input.is_empty() {
-            Selection::from_bookmarks_with_selected(&self.bookmarks, self.selection.selected)
+            Selection::from_bookmarks_with_selected(
+                &self.bookmarks,
+                &self.visits,
+                self.selection.selected,
+            )
         } else {
-            let candidates = search::find_matches(&self.matcher, &self.bookmarks, input);
+            let candidates = search::find_matches(
+                &self.matcher,
+                &self.bookmarks,
+                config::get().sort,
+                &self.visits,
+                input,
+            );
             Selection::from_candidates_with_selected(candidates, self.selection.selected)
         };
-        self.selection = selection;
+        self.selection = Selection {
+            offset: self.selection.offset,
+            ..selection
+        };
     }
 
     pub fn enter_mode(&mut self, mode: Mode) {
+        if mode.is_base() {
+            self.base_mode = mode;
+        }
+        if mode == Mode::Help {
+            self.help = HelpState::default();
+        }
+        if mode == Mode::Rename {
+            // Only bookmarks of writable sources can be renamed
+            match self.selected_bookmark() {
+                Some(bm) if bm.source.is_writable() => self.rename = Input::from_query(&bm.name),
+                _ => return,
+            }
+        }
         self.mode = mode;
     }
+
+    /// Whether another bookmark of the same source has the name already.
+    pub fn is_name_taken(&self, bookmark: &Bookmark, name: &str) -> bool {
+        self.bookmarks
+            .iter()
+            .any(|b| b.name == name && b.source == bookmark.source && **b != *bookmark)
+    }
+
+    fn edit_help_query(&self, query: Input) -> BrowseState {
+        BrowseState {
+            help: HelpState { query, scroll: 0 },
+            ..self.clone()
+        }
+    }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Command {
     ExitApp,
     EnterMode(Mode),
+    LeaveMode,
     EnterSelDir,
+    EnterParentDir,
     OpenSelInEditor,
+    OpenSelWithSystem,
+    OpenSelDirWithSystem,
+    CopySelPath,
+    InsertSelPath,
     DefaultAction,
+    RunCustomAction(usize),
+    OpenActionMenu,
+    RunMenuItem,
+    OpenSuggestions,
+    BookmarkSuggestion,
     DelSelBookmark,
+    RenameSelBookmark,
     InsertChar(char),
     DeleteCharBack,
     ClearInput,
     MoveSel(MoveDirection),
+    MovePage(MoveDirection),
+    MoveHalfPage(MoveDirection),
+    MoveFirst,
+    MoveLast,
+    SelectLine(usize),
+    HistoryPrev,
+    HistoryNext,
+    HistorySearch,
+    ScrollHelp(MoveDirection),
+}
+
+// Commands that can be bound to keys by name, along with their descriptions
+const NAMED_COMMANDS: [(&str, Command, &str); 36] = [
+    ("exit", Command::ExitApp, "Exit application"),
+    (
+        "default-action",
+        Command::DefaultAction,
+        "Run the default action",
+    ),
+    (
+        "jump",
+        Command::EnterSelDir,
+        "Jump to the bookmarked directory",
+    ),
+    (
+        "jump-parent",
+        Command::EnterParentDir,
+        "Jump to the parent directory",
+    ),
+    (
+        "edit",
+        Command::OpenSelInEditor,
+        "Open the bookmarked location in $EDITOR",
+    ),
+    (
+        "open",
+        Command::OpenSelWithSystem,
+        "Open with the default application",
+    ),
+    (
+        "open-dir",
+        Command::OpenSelDirWithSystem,
+        "Open the directory in the file manager",
+    ),
+    (
+        "copy",
+        Command::CopySelPath,
+        "Copy the path to the clipboard",
+    ),
+    (
+        "insert-path",
+        Command::InsertSelPath,
+        "Insert the path into the command line",
+    ),
+    (
+        "delete",
+        Command::EnterMode(Mode::PendingDelete),
+        "Delete bookmark",
+    ),
+    (
+        "confirm-delete",
+        Command::DelSelBookmark,
+        "Confirm deletion",
+    ),
+    (
+        "rename",
+        Command::EnterMode(Mode::Rename),
+        "Rename bookmark",
+    ),
+    (
+        "confirm-rename",
+        Command::RenameSelBookmark,
+        "Confirm the new name",
+    ),
+    (
+        "action-menu",
+        Command::OpenActionMenu,
+        "Show actions for the bookmark",
+    ),
+    (
+        "run-action",
+        Command::RunMenuItem,
+        "Run the selected action",
+    ),
+    (
+        "suggestions",
+        Command::OpenSuggestions,
+        "Show often visited directories to bookmark",
+    ),
+    (
+        "bookmark-suggestion",
+        Command::BookmarkSuggestion,
+        "Bookmark the selected directory",
+    ),
+    ("cancel", Command::LeaveMode, "Go back"),
+    (
+        "insert",
+        Command::EnterMode(Mode::Normal),
+        "Focus the query",
+    ),
+    (
+        "vi-normal",
+        Command::EnterMode(Mode::ViNormal),
+        "Navigate with vi keys",
+    ),
+    ("help", Command::EnterMode(Mode::Help), "Show help"),
+    (
+        "backward-delete-char",
+        Command::DeleteCharBack,
+        "Delete previous character",
+    ),
+    ("clear-input", Command::ClearInput, "Clear input"),
+    (
+        "select-next",
+        Command::MoveSel(MoveDirection::Down),
+        "Select next",
+    ),
+    (
+        "select-prev",
+        Command::MoveSel(MoveDirection::Up),
+        "Select previous",
+    ),
+    (
+        "page-down",
+        Command::MovePage(MoveDirection::Down),
+        "Select next page",
+    ),
+    (
+        "page-up",
+        Command::MovePage(MoveDirection::Up),
+        "Select previous page",
+    ),
+    (
+        "half-page-down",
+        Command::MoveHalfPage(MoveDirection::Down),
+        "Select next half-page",
+    ),
+    (
+        "half-page-up",
+        Command::MoveHalfPage(MoveDirection::Up),
+        "Select previous half-page",
+    ),
+    ("first", Command::MoveFirst, "Select first"),
+    ("last", Command::MoveLast, "Select last"),
+    (
+        "history-search",
+        Command::HistorySearch,
+        "Search query history",
+    ),
+    (
+        "history-prev",
+        Command::HistoryPrev,
+        "Previous query from history",
+    ),
+    (
+        "history-next",
+        Command::HistoryNext,
+        "Next query from history",
+    ),
+    (
+        "scroll-down",
+        Command::ScrollHelp(MoveDirection::Down),
+        "Scroll down",
+    ),
+    (
+        "scroll-up",
+        Command::ScrollHelp(MoveDirection::Up),
+        "Scroll up",
+    ),
+];
+
+// Custom actions from the config are bound to keys as `action:<name>`
+pub const CUSTOM_ACTION_PREFIX: &str = "action:";
+
+impl Command {
+    pub fn from_name(name: &str) -> Option<(Command, &'static str)> {
+        if let Some(action_name) = name.strip_prefix(CUSTOM_ACTION_PREFIX) {
+            let actions = &config::get().actions;
+            return actions
+                .iter()
+                .position(|action| action.name == action_name)
+                .map(|idx| (Command::RunCustomAction(idx), actions[idx].desc()));
+        }
+        NAMED_COMMANDS
+            .iter()
+            .find(|(cmd_name, _, _)| *cmd_name == name)
+            .map(|(_, cmd, desc)| (cmd.clone(), *desc))
+    }
+
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        NAMED_COMMANDS.iter().map(|(name, _, _)| *name)
+    }
+
+    pub fn category(&self) -> Category {
+        match self {
+            Command::DefaultAction
+            | Command::EnterSelDir
+            | Command::EnterParentDir
+            | Command::OpenSelInEditor
+            | Command::OpenSelWithSystem
+            | Command::OpenSelDirWithSystem
+            | Command::CopySelPath
+            | Command::InsertSelPath
+            | Command::RunCustomAction(_)
+            | Command::OpenActionMenu
+            | Command::RunMenuItem
+            | Command::OpenSuggestions
+            | Command::BookmarkSuggestion
+            | Command::DelSelBookmark
+            | Command::RenameSelBookmark
+            | Command::EnterMode(Mode::PendingDelete)
+            | Command::EnterMode(Mode::Rename) => Category::Actions,
+            Command::MoveSel(_)
+            | Command::MovePage(_)
+            | Command::MoveHalfPage(_)
+            | Command::MoveFirst
+            | Command::MoveLast
+            | Command::SelectLine(_)
+            | Command::ScrollHelp(_) => Category::Navigation,
+            Command::InsertChar(_) | Command::DeleteCharBack | Command::ClearInput => {
+                Category::Query
+            }
+            Command::HistoryPrev | Command::HistoryNext | Command::HistorySearch => {
+                Category::History
+            }
+            Command::ExitApp | Command::EnterMode(_) | Command::LeaveMode => Category::General,
+        }
+    }
+
+    /// Short label for the status bar, for the commands worth showing there.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Command::EnterMode(Mode::Help) => Some("Help"),
+            Command::EnterMode(Mode::Normal) => Some("Search"),
+            Command::DefaultAction => Some("DWIM"),
+            Command::EnterSelDir => Some("Jump"),
+            Command::OpenSelInEditor => Some("Edit"),
+            Command::OpenActionMenu => Some("Actions"),
+            Command::RunMenuItem => Some("Run"),
+            Command::BookmarkSuggestion => Some("Bookmark"),
+            Command::RenameSelBookmark => Some("Rename"),
+            Command::LeaveMode => Some("Back"),
+            Command::ScrollHelp(_) => Some("Scroll"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    Actions,
+    Navigation,
+    Query,
+    History,
+    General,
+}
+
+impl Category {
+    pub fn title(&self) -> &'static str {
+        match self {
+            Category::Actions => "Actions",
+            Category::Navigation => "Navigation",
+            Category::Query => "Query",
+            Category::History => "History",
+            Category::General => "General",
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Mode {
     Normal,
+    // Navigation with vi keys, while typing goes to the query in Normal mode
+    ViNormal,
     PendingDelete,
+    ActionMenu,
+    Suggestions,
+    Rename,
     Help,
 }
 
+impl Mode {
+    pub const ALL: [Mode; 7] = [
+        Mode::Normal,
+        Mode::ViNormal,
+        Mode::PendingDelete,
+        Mode::ActionMenu,
+        Mode::Suggestions,
+        Mode::Rename,
+        Mode::Help,
+    ];
+
+    /// Whether the mode is for browsing bookmarks rather than a dialog or the help.
+    pub fn is_base(&self) -> bool {
+        matches!(self, Mode::Normal | Mode::ViNormal)
+    }
+
+    pub fn from_name(name: &str) -> Option<Mode> {
+        Mode::ALL
+            .into_iter()
+            .find(|mode| <&str>::from(*mode) == name)
+    }
+}
+
 impl From<Mode> for &'static str {
     fn from(mode: Mode) -> Self {
         match mode {
             Mode::Normal => "normal",
+            Mode::ViNormal => "vi_normal",
             Mode::PendingDelete => "pending_delete",
+            Mode::ActionMenu => "action_menu",
+            Mode::Suggestions => "suggestions",
+            Mode::Rename => "rename",
             Mode::Help => "help",
         }
     }
 }
 
+// Variants are named after the actions they stand for
+#[allow(clippy::enum_variant_names)]
 pub enum Action {
-    ChangeDirAction { dest: PathBuf },
-    OpenInEditorAction { dest: PathBuf },
+    ChangeDirAction {
+        dest: PathBuf,
+    },
+    OpenInEditorAction {
+        dest: PathBuf,
+    },
+    // Opened by the system's default application rather than the shell
+    OpenWithSystemAction {
+        dest: PathBuf,
+    },
+    // Only the quoted path, for the shell widgets to insert into the command line
+    InsertPathAction {
+        dest: PathBuf,
+    },
+    // Put on the clipboard rather than printed
+    CopyPathAction {
+        dest: PathBuf,
+    },
+    // A command from the config with placeholders for the bookmark
+    CustomAction {
+        command: String,
+        name: String,
+        dest: PathBuf,
+        dir: PathBuf,
+    },
+}
+
+// A literal path isn't expanded as a wildcard pattern, so `[` and `*` in it are safe
+fn change_location(dest: &Path) -> String {
+    format!(
+        "Push-Location -LiteralPath {}",
+        shell::quote(dest, shell::OutputType::PowerShell)
+    )
+}
+
+// `$EDITOR` can have arguments, like the configured editor, so it's split into words for the
+// shells that don't do it themselves
+fn open_in_editor(editor: &str, dest: &Path, out_type: shell::OutputType) -> String {
+    use shell::OutputType::*;
+
+    let quoted = shell::quote(dest, out_type);
+    match out_type {
+        Plain => dest.to_string_lossy().to_string(),
+        Posix | Zsh | Fish => format!("{editor} {quoted}"),
+        PowerShell if editor == "$EDITOR" => {
+            format!("$editor, $editorArgs = -split $env:EDITOR; & $editor @editorArgs {quoted}")
+        }
+        PowerShell => format!("& {editor} {quoted}"),
+        Elvish if editor == "$EDITOR" => format!(
+            "use str; var editor @args = (str:fields $E:EDITOR); (external $editor) $@args {quoted}"
+        ),
+        Xonsh if editor == "$EDITOR" => format!("@($EDITOR.split()) {quoted}"),
+        Elvish | Xonsh => format!("{editor} {quoted}"),
+        Nushell => {
+            let command = match editor {
+                "$EDITOR" => format!(
+                    "let editor = ($env.EDITOR | split row ' ' | where $it != ''); ^($editor.0) ...($editor | skip 1) {quoted}"
+                ),
+                editor => format!("{editor} {quoted}"),
+            };
+            nu_action("run", "command", command)
+        }
+    }
+}
+
+// Nushell can't eval code, so its integration gets a record describing what to do
+fn nu_action(action: &str, field: &str, value: impl AsRef<OsStr>) -> String {
+    format!(
+        "{{action: {}, {}: {}}}",
+        action,
+        field,
+        shell::quote(value, shell::OutputType::Nushell)
+    )
 }
 
 impl shell::Output for Action {
@@ -368,35 +1305,254 @@ impl shell::Output for Action {
 
         match self {
             Action::ChangeDirAction { dest } => {
-                let dest_string = simplify_path(dest).to_string_lossy();
-
+                let dest = simplify_path(dest);
                 let out = match out_type {
-                    Plain => dest_string.to_string(),
-                    Posix | Fish => format!("cd '{}'", dest_string),
-                    PowerShell => format!("Push-Location '{}'", dest_string),
+                    Plain => dest.to_string_lossy().to_string(),
+                    Posix | Zsh | Fish | Elvish | Xonsh => {
+                        format!("cd {}", shell::quote(dest, out_type))
+                    }
+                    PowerShell => change_location(dest),
+                    Nushell => nu_action("cd", "path", dest),
                 };
                 Some(out)
             }
             Action::OpenInEditorAction { dest } => {
-                let dest_string = simplify_path(dest).to_string_lossy();
-                let out = if is_editor_set() {
-                    match out_type {
-                        Plain => dest_string.to_string(),
-                        Posix | Fish => format!("$EDITOR '{}'", dest_string),
-                        PowerShell => format!("Push-Location '{}'", dest_string),
-                    }
+                let dest = simplify_path(dest);
+                let out = if let Some(editor) = editor_command() {
+                    open_in_editor(&editor, dest, out_type)
                 } else {
                     match out_type {
                         Plain => "$EDITOR environment variable is not set".to_string(),
-                        Posix | Fish => {
+                        Posix | Zsh | Fish => {
                             "echo \"\\$EDITOR environment variable is not set\"".to_string()
                         }
-                        PowerShell => format!("Push-Location '{}'", dest_string),
+                        PowerShell => {
+                            "Write-Output '$EDITOR environment variable is not set'".to_string()
+                        }
+                        Nushell => nu_action(
+                            "print",
+                            "message",
+                            "$EDITOR environment variable is not set",
+                        ),
+                        Elvish => "echo '$EDITOR environment variable is not set'".to_string(),
+                        Xonsh => "print('$EDITOR environment variable is not set')".to_string(),
                     }
                 };
 
                 Some(out)
             }
+            Action::InsertPathAction { dest } => {
+                let dest = simplify_path(dest);
+                let out = match out_type {
+                    Plain => dest.to_string_lossy().to_string(),
+                    _ => shell::quote(dest, out_type),
+                };
+                Some(out)
+            }
+            Action::OpenWithSystemAction { .. } | Action::CopyPathAction { .. } => None,
+            Action::CustomAction {
+                command,
+                name,
+                dest,
+                dir,
+            } => {
+                let vars = [
+                    ("dest", simplify_path(dest).as_os_str()),
+                    ("dir", simplify_path(dir).as_os_str()),
+                    ("name", OsStr::new(name)),
+                ];
+                // The command was checked when the config was loaded
+                let command = shell::expand_template(command, &vars, out_type).ok()?;
+                match out_type {
+                    Nushell => Some(nu_action("run", "command", command)),
+                    _ => Some(command),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selection(len: usize, selected: usize) -> Selection {
+        Selection::from_candidates_with_selected((0..len).collect(), Some(selected))
+    }
+
+    #[test]
+    fn move_by_clamps_at_the_ends() {
+        assert_eq!(selection(10, 3).move_by(4).selected, Some(7));
+        assert_eq!(selection(10, 3).move_by(20).selected, Some(9));
+        assert_eq!(selection(10, 3).move_by(-20).selected, Some(0));
+        assert_eq!(selection(0, 0).move_by(5).selected, None);
+    }
+
+    #[test]
+    fn move_highlight_wraps_around_only_when_cycling() {
+        let last = selection(5, 4);
+        assert_eq!(
+            last.move_highlight(&MoveDirection::Down, false).selected,
+            Some(4)
+        );
+        assert_eq!(
+            last.move_highlight(&MoveDirection::Down, true).selected,
+            Some(0)
+        );
+        let first = selection(5, 0);
+        assert_eq!(
+            first.move_highlight(&MoveDirection::Up, false).selected,
+            Some(0)
+        );
+        assert_eq!(
+            first.move_highlight(&MoveDirection::Up, true).selected,
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn scroll_keeps_context_around_the_highlight() {
+        // Moving down past the bottom keeps two rows below the highlighted one
+        assert_eq!(selection(100, 10).scroll(10).offset, 3);
+        // Moving back up keeps two rows above
+        let sel = Selection {
+            offset: 20,
+            ..selection(100, 21)
+        };
+        assert_eq!(sel.scroll(10).offset, 19);
+        // Rows in the middle of the view don't scroll
+        let sel = Selection {
+            offset: 5,
+            ..selection(100, 10)
+        };
+        assert_eq!(sel.scroll(10).offset, 5);
+    }
+
+    #[test]
+    fn scroll_stops_at_the_end_of_the_list() {
+        assert_eq!(selection(100, 99).scroll(10).offset, 90);
+        assert_eq!(selection(5, 4).scroll(10).offset, 0);
+        // Too short for context rows
+        assert_eq!(selection(100, 50).scroll(1).offset, 50);
+        assert_eq!(selection(100, 50).scroll(0).offset, 0);
+    }
+
+    #[test]
+    fn powershell_opens_the_editor() {
+        let dest = Path::new("/srv/it's");
+        assert_eq!(
+            open_in_editor("$EDITOR", dest, shell::OutputType::PowerShell),
+            "$editor, $editorArgs = -split $env:EDITOR; & $editor @editorArgs '/srv/it''s'"
+        );
+        assert_eq!(
+            open_in_editor("code -w", dest, shell::OutputType::PowerShell),
+            "& code -w '/srv/it''s'"
+        );
+    }
+
+    #[test]
+    fn nushell_splits_the_editor_into_words() {
+        let dest = Path::new("/srv/it's");
+        assert_eq!(
+            open_in_editor("$EDITOR", dest, shell::OutputType::Nushell),
+            r#"{action: run, command: "let editor = ($env.EDITOR | split row ' ' | where $it != ''); ^($editor.0) ...($editor | skip 1) \"/srv/it's\""}"#
+        );
+    }
+
+    fn history_state(input: &str) -> BrowseState {
+        let matcher = Matcher::from_config(&config::MatcherConfig::default());
+        let history = ["alpha", "beta", "gamma"].map(String::from).to_vec();
+        let mut state = BrowseState::new(
+            vec![],
+            Arc::new(matcher),
+            Arc::new(Visits::default()),
+            history,
+            false,
+            false,
+        );
+        state.input = Input::from_query(input);
+        state
+    }
+
+    async fn run(state: &BrowseState, cmd: Command) -> BrowseState {
+        match state.handle_command(&cmd).await.unwrap() {
+            HandleResult::Continue(state) => state,
+            HandleResult::Terminate(_) => panic!("{cmd:?} terminated the browser"),
+        }
+    }
+
+    #[tokio::test]
+    async fn history_navigation_stops_at_the_oldest_and_restores_the_typed_query() {
+        let mut state = history_state("typed");
+        let mut shown = vec![];
+        for cmd in [
+            Command::HistoryPrev,
+            Command::HistoryPrev,
+            Command::HistoryPrev,
+            Command::HistoryPrev,
+            Command::HistoryNext,
+            Command::HistoryNext,
+            Command::HistoryNext,
+            Command::HistoryNext,
+        ] {
+            state = run(&state, cmd).await;
+            shown.push(state.input.to_string());
+        }
+        assert_eq!(
+            shown,
+            ["gamma", "beta", "alpha", "alpha", "beta", "gamma", "typed", "typed"]
+        );
+        assert_eq!(state.history_nav, None);
+    }
+
+    #[tokio::test]
+    async fn typing_leaves_the_history() {
+        let state = run(&history_state("typed"), Command::HistoryPrev).await;
+        assert_eq!(
+            state.history_nav,
+            Some(HistoryNav {
+                pos: 2,
+                pattern: "typed".to_string()
+            })
+        );
+        let state = run(&state, Command::InsertChar('!')).await;
+        assert_eq!(state.input.to_string(), "gamma!");
+        assert_eq!(state.history_nav, None);
+        // So the history starts over from the most recent query
+        let state = run(&state, Command::HistoryPrev).await;
+        assert_eq!(state.input.to_string(), "gamma");
+    }
+
+    #[tokio::test]
+    async fn history_search_finds_older_matches_of_the_typed_query() {
+        let state = run(&history_state("a"), Command::HistorySearch).await;
+        assert_eq!(state.input.to_string(), "gamma");
+        let state = run(&state, Command::HistorySearch).await;
+        assert_eq!(state.input.to_string(), "beta");
+        let state = run(&state, Command::HistorySearch).await;
+        assert_eq!(state.input.to_string(), "alpha");
+        // Nothing older matches
+        let state = run(&state, Command::HistorySearch).await;
+        assert_eq!(state.input.to_string(), "alpha");
+        assert_eq!(state.history_nav.as_ref().unwrap().pattern, "a");
+
+        let state = run(&history_state("zz"), Command::HistorySearch).await;
+        assert_eq!(state.input.to_string(), "zz");
+        assert_eq!(state.history_nav, None);
+    }
+
+    #[tokio::test]
+    async fn an_empty_history_changes_nothing() {
+        let mut state = history_state("typed");
+        state.history.clear();
+        for cmd in [
+            Command::HistoryPrev,
+            Command::HistoryNext,
+            Command::HistorySearch,
+        ] {
+            let new_state = run(&state, cmd).await;
+            assert_eq!(new_state.input.to_string(), "typed");
+            assert_eq!(new_state.history_nav, None);
         }
     }
 }
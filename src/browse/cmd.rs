@@ -1,39 +1,74 @@
-use std::io::{self, Stderr};
+use std::{
+    collections::BTreeMap,
+    io::{self, Stderr, Write},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+use crossterm::cursor::{MoveTo, Show};
+use crossterm::{event::EventStream, execute};
 use crossterm::{
-    event::Event,
-    terminal::{EnterAlternateScreen, LeaveAlternateScreen},
+    event::{
+        DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
+    terminal::{Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use crossterm::{event::EventStream, execute};
 use futures::{stream, TryStreamExt};
-use fuzzy_matcher::skim::SkimMatcherV2;
 use tokio::time::Instant;
 use tokio_stream::StreamExt;
-use tui::{backend::CrosstermBackend, Terminal};
+use tracing::warn;
+use tui::{backend::CrosstermBackend, layout::Rect, Terminal, TerminalOptions, Viewport};
 
 use super::*;
-use crate::keys::{self, arrow_up, ctrl_K, ctrl_p};
-use crate::keys::{ctrl_k, ModeMap};
+use crate::keys::{self, Combo, KeyResult, KeySpec, ModeMap};
 use crate::{
     bookmarks::read_bookmarks,
-    keys::{arrow_down, ctrl_n},
+    cli::{BrowseCmd, Height},
+    copy::copy_to_clipboard,
+    history::{append_history, read_history},
+    visits::{read_visits, record_visit},
 };
 
-pub async fn browse_cmd() -> Result<Option<Action>> {
-    setup_terminal()?;
-    let output = interact().await;
-    restore_terminal()?;
+pub async fn browse_cmd(opts: BrowseCmd) -> Result<Option<Action>> {
+    // Set up key bindings before taking over the terminal, so that config problems are visible
+    let keybinds = setup_keybindings(opts.vi || config::get().vi_mode)?;
+    let inline_area = setup_terminal(opts.height)?;
+    let opener = opts.opener.clone();
+    let output = interact(opts, keybinds, inline_area).await;
+    restore_terminal(inline_area)?;
+
+    // GUI apps are started after the terminal is back to normal
+    match &output {
+        Ok(Some(Action::OpenWithSystemAction { dest })) => {
+            shell::open_with_system(dest, opener.as_deref())?
+        }
+        Ok(Some(Action::CopyPathAction { dest })) => {
+            copy_to_clipboard(&simplify_path(dest).to_string_lossy())?
+        }
+        _ => {}
+    }
     output
 }
 
-async fn interact() -> Result<Option<Action>> {
+async fn interact(
+    opts: BrowseCmd,
+    keybinds: ModeMap<Command>,
+    inline_area: Option<Rect>,
+) -> Result<Option<Action>> {
     let bookmarks = read_bookmarks().await?;
-    let matcher = SkimMatcherV2::default();
-    let keybinds = setup_keybindings();
-
+    let history = read_history().await?;
+    let visits = read_visits().await?;
+    let matcher = Matcher::from_config(&config::get().matcher);
     let backend = CrosstermBackend::new(io::stderr());
-    let mut terminal = Terminal::new(backend)?;
+    let mut terminal = match inline_area {
+        None => Terminal::new(backend)?,
+        Some(area) => Terminal::with_options(
+            backend,
+            TerminalOptions {
+                viewport: Viewport::fixed(area),
+            },
+        )?,
+    };
 
     // Setup an event loop
     let ticks = stream::repeat(Tick)
@@ -45,37 +80,123 @@ async fn interact() -> Result<Option<Action>> {
     let user_events = EventStream::new().map_ok(SystemEvent::from);
     let mut system_events = ticks.merge(user_events);
 
-    let mut app_state = BrowseState::new(bookmarks, Arc::new(matcher));
+    let mut app_state = BrowseState::new(
+        bookmarks,
+        Arc::new(matcher),
+        Arc::new(visits),
+        history,
+        opts.cycle,
+        opts.insert,
+    );
+    if !opts.query.is_empty() {
+        app_state.input = Input::from_query(&opts.query.join(" "));
+        app_state.update_selection();
+    }
 
     loop {
         let event: SystemEvent = TryStreamExt::try_next(&mut system_events)
             .await?
             .expect("Ticks are always present");
 
+        let query = app_state.input.to_string();
         match event_loop(event, app_state, &keybinds, &mut terminal).await? {
             HandleResult::Continue(new_state) => app_state = new_state,
-            HandleResult::Terminate(action) => return Ok(action),
+            HandleResult::Terminate(action) => {
+                if let Some(action) = &action {
+                    // The bookmark is picked already, so a failed write shouldn't lose it
+                    if let Err(err) = append_history(&query).await {
+                        warn!("Couldn't save the query to the history: {:#}", err);
+                    }
+                    // Changing to a bookmark through the browser counts as a visit too
+                    if let Action::ChangeDirAction { dest } = action {
+                        if let Err(err) = record_visit(dest).await {
+                            warn!("Couldn't record the visit: {:#}", err);
+                        }
+                    }
+                }
+                return Ok(action);
+            }
         }
     }
 }
 
-fn setup_terminal() -> Result<()> {
+// Two clicks on the same line within this interval are treated as a double click
+const DOUBLE_CLICK_MS: Duration = Duration::from_millis(400);
+
+// The browser needs room for the borders, the input, the bottom bar and at least one bookmark
+const MIN_INLINE_HEIGHT: u16 = 7;
+
+/// Prepare the terminal for drawing the browser.
+///
+/// Without a height the browser takes over the alternate screen. With a height it is drawn inline
+/// below the cursor, and the area reserved for it is returned.
+fn setup_terminal(height: Option<Height>) -> Result<Option<Rect>> {
     crossterm::terminal::enable_raw_mode()?;
-    Ok(execute!(io::stderr(), EnterAlternateScreen)?)
+    let area = setup_screen(height);
+    // Don't leave the shell in raw mode when the screen can't be set up
+    if area.is_err() {
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+    area
+}
+
+fn setup_screen(height: Option<Height>) -> Result<Option<Rect>> {
+    let height = match height {
+        None => {
+            execute!(io::stderr(), EnterAlternateScreen, EnableMouseCapture)?;
+            return Ok(None);
+        }
+        Some(height) => height,
+    };
+
+    let (width, term_height) = crossterm::terminal::size()?;
+    let rows = height
+        .rows(term_height)
+        .max(MIN_INLINE_HEIGHT)
+        .min(term_height);
+
+    // Scroll the terminal if there are not enough lines below the cursor
+    let mut stderr = io::stderr();
+    for _ in 1..rows {
+        write!(stderr, "\r\n")?;
+    }
+    stderr.flush()?;
+    let (_, bottom) = crossterm::cursor::position()?;
+    let top = (bottom + 1).saturating_sub(rows);
+    execute!(stderr, MoveTo(0, top), EnableMouseCapture)?;
+
+    Ok(Some(Rect::new(0, top, width, rows)))
 }
 
-fn restore_terminal() -> Result<()> {
+fn restore_terminal(inline_area: Option<Rect>) -> Result<()> {
     crossterm::terminal::disable_raw_mode()?;
-    Ok(execute!(io::stderr(), LeaveAlternateScreen)?)
+    match inline_area {
+        None => Ok(execute!(
+            io::stderr(),
+            DisableMouseCapture,
+            LeaveAlternateScreen
+        )?),
+        // Wipe the browser and leave the cursor where it was, so the scrollback stays intact
+        Some(area) => Ok(execute!(
+            io::stderr(),
+            DisableMouseCapture,
+            MoveTo(0, area.y),
+            Clear(ClearType::FromCursorDown),
+            Show
+        )?),
+    }
 }
 
 async fn event_loop(
     event: SystemEvent,
-    app_state: BrowseState,
+    mut app_state: BrowseState,
     keybinds: &ModeMap<Command>,
     terminal: &mut Terminal<CrosstermBackend<Stderr>>,
 ) -> Result<HandleResult> {
-    let (should_repaint, new_state) = match event {
+    let area = frame_area(terminal)?;
+    app_state.list_height = ui::list_height(area);
+    let timeout = Duration::from_millis(config::get().key_sequence_timeout);
+    let (should_repaint, mut new_state) = match event {
         SystemEvent::Timer(_) => match app_state.last_refresh_at {
             None => (
                 true,
@@ -84,26 +205,51 @@ async fn event_loop(
                     ..app_state.clone()
                 },
             ),
+            // Drop the key sequence prefix that wasn't completed in time
+            Some(_)
+                if app_state.pending.is_some() && app_state.pending_keys(timeout).is_empty() =>
+            {
+                (
+                    true,
+                    BrowseState {
+                        pending: None,
+                        last_refresh_at: Instant::now().into(),
+                        ..app_state.clone()
+                    },
+                )
+            }
             Some(_) => (false, app_state.clone()),
         },
         SystemEvent::User(Event::Key(k)) => {
-            let command = keybinds.process(app_state.mode, k);
-            let result = match command {
-                None => HandleResult::Continue(app_state.clone()),
-                Some(command) => app_state.handle_command(&command).await?,
-            };
-            match result {
+            let pending_keys = app_state.pending_keys(timeout);
+            let (command, pending) =
+                match keybinds.process_sequence(app_state.mode, pending_keys, k) {
+                    KeyResult::Command(command) => (Some(command), None),
+                    KeyResult::Pending(keys) => (
+                        None,
+                        Some(PendingKeys {
+                            keys,
+                            since: Instant::now(),
+                        }),
+                    ),
+                    KeyResult::Unbound => (None, None),
+                };
+            match run_command(&app_state, command).await? {
                 HandleResult::Continue(mut new_state) => {
-                    if new_state != app_state {
-                        new_state.last_refresh_at = Instant::now().into();
-                        (true, new_state)
-                    } else {
-                        (false, new_state)
-                    }
+                    new_state.pending = pending;
+                    (new_state != app_state, new_state)
                 }
                 act @ HandleResult::Terminate(_) => return Ok(act),
             }
         }
+        SystemEvent::User(Event::Mouse(m)) => {
+            let list_area = ui::list_area(area);
+            let command = mouse_command(&mut app_state, m, list_area);
+            match run_command(&app_state, command).await? {
+                HandleResult::Continue(new_state) => (new_state != app_state, new_state),
+                act @ HandleResult::Terminate(_) => return Ok(act),
+            }
+        }
         _ => (
             true,
             BrowseState {
@@ -113,6 +259,14 @@ async fn event_loop(
         ),
     };
 
+    new_state.selection = new_state.selection.scroll(new_state.list_height);
+    if new_state.mode == Mode::Help {
+        // Stop at the last page, so scrolling back up starts right away
+        let line_count = ui::help_lines(keybinds, &new_state).len();
+        let max_scroll = line_count.saturating_sub(ui::help_height(area));
+        new_state.help.scroll = new_state.help.scroll.min(max_scroll);
+    }
+
     if should_repaint {
         ui::draw_ui(terminal, &new_state, keybinds)?;
     }
@@ -120,133 +274,454 @@ async fn event_loop(
     Ok(HandleResult::Continue(new_state))
 }
 
-fn setup_keybindings() -> ModeMap<Command> {
-    let mut mapping = ModeMap::new();
+// Area the browser is drawn in, which is only a part of the terminal in inline mode
+fn frame_area(terminal: &mut Terminal<CrosstermBackend<Stderr>>) -> Result<Rect> {
+    terminal.autoresize()?;
+    Ok(terminal.get_frame().size())
+}
+
+async fn run_command(app_state: &BrowseState, command: Option<Command>) -> Result<HandleResult> {
+    let mut result = match command {
+        None => HandleResult::Continue(app_state.clone()),
+        Some(command) => app_state.handle_command(&command).await?,
+    };
+    if let HandleResult::Continue(new_state) = &mut result {
+        if new_state != app_state {
+            new_state.last_refresh_at = Instant::now().into();
+        }
+    }
+    Ok(result)
+}
+
+fn mouse_command(
+    app_state: &mut BrowseState,
+    mouse: MouseEvent,
+    list_area: Rect,
+) -> Option<Command> {
+    if !app_state.mode.is_base() {
+        return None;
+    }
+
+    match mouse.kind {
+        MouseEventKind::ScrollDown => Some(Command::MoveSel(MoveDirection::Down)),
+        MouseEventKind::ScrollUp => Some(Command::MoveSel(MoveDirection::Up)),
+        MouseEventKind::Down(MouseButton::Left) => {
+            let in_list = mouse.column >= list_area.x
+                && mouse.column < list_area.x + list_area.width
+                && mouse.row >= list_area.y
+                && mouse.row < list_area.y + list_area.height;
+            if !in_list {
+                return None;
+            }
+            let line = app_state.selection.offset + (mouse.row - list_area.y) as usize;
+            if line >= app_state.selection.candidates.len() {
+                return None;
+            }
+
+            let now = Instant::now();
+            let is_double_click = matches!(
+                app_state.last_click,
+                Some((clicked_at, clicked_line))
+                    if clicked_line == line && now.duration_since(clicked_at) < DOUBLE_CLICK_MS
+            );
+            if is_double_click {
+                app_state.last_click = None;
+                Some(Command::DefaultAction)
+            } else {
+                app_state.last_click = Some((now, line));
+                Some(Command::SelectLine(line))
+            }
+        }
+        _ => None,
+    }
+}
 
+// Built-in key bindings, which can be overridden or unbound in the config
+const DEFAULT_BINDINGS: [(Mode, &str, &str); 60] = [
     // Normal mode mappings
-    mapping.bind(
-        Mode::Normal,
-        keys::ctrl_c(),
-        Command::ExitApp,
-        "Exit application",
-    );
+    (Mode::Normal, "ctrl-c", "exit"),
+    (Mode::Normal, "ctrl-n", "select-next"),
+    (Mode::Normal, "down", "select-next"),
+    (Mode::Normal, "ctrl-p", "select-prev"),
+    (Mode::Normal, "up", "select-prev"),
+    (Mode::Normal, "pagedown", "page-down"),
+    (Mode::Normal, "pageup", "page-up"),
+    (Mode::Normal, "ctrl-d", "half-page-down"),
+    (Mode::Normal, "ctrl-u", "half-page-up"),
+    (Mode::Normal, "home", "first"),
+    (Mode::Normal, "end", "last"),
+    (Mode::Normal, "enter", "default-action"),
+    (Mode::Normal, "ctrl-j", "jump"),
+    (Mode::Normal, "ctrl-o", "edit"),
+    (Mode::Normal, "alt-o", "open"),
+    (Mode::Normal, "alt-c", "copy"),
+    (Mode::Normal, "ctrl-k", "delete"),
+    (Mode::Normal, "ctrl-K", "delete"),
+    (Mode::Normal, "f2", "rename"),
+    (Mode::Normal, "backspace", "backward-delete-char"),
+    (Mode::Normal, "ctrl-backspace", "clear-input"),
+    (Mode::Normal, "ctrl-r", "history-search"),
+    (Mode::Normal, "alt-p", "history-prev"),
+    (Mode::Normal, "alt-n", "history-next"),
+    (Mode::Normal, "tab", "action-menu"),
+    (Mode::Normal, "alt-s", "suggestions"),
+    (Mode::Normal, "f1", "help"),
+    // PendingDelete mode mappings
+    (Mode::PendingDelete, "ctrl-c", "exit"),
+    (Mode::PendingDelete, "y", "confirm-delete"),
+    (Mode::PendingDelete, "n", "cancel"),
+    // ActionMenu mode mappings
+    (Mode::ActionMenu, "ctrl-c", "exit"),
+    (Mode::ActionMenu, "esc", "cancel"),
+    (Mode::ActionMenu, "tab", "cancel"),
+    (Mode::ActionMenu, "enter", "run-action"),
+    (Mode::ActionMenu, "down", "select-next"),
+    (Mode::ActionMenu, "ctrl-n", "select-next"),
+    (Mode::ActionMenu, "up", "select-prev"),
+    (Mode::ActionMenu, "ctrl-p", "select-prev"),
+    // Suggestions mode mappings
+    (Mode::Suggestions, "ctrl-c", "exit"),
+    (Mode::Suggestions, "esc", "cancel"),
+    (Mode::Suggestions, "alt-s", "cancel"),
+    (Mode::Suggestions, "enter", "bookmark-suggestion"),
+    (Mode::Suggestions, "down", "select-next"),
+    (Mode::Suggestions, "ctrl-n", "select-next"),
+    (Mode::Suggestions, "up", "select-prev"),
+    (Mode::Suggestions, "ctrl-p", "select-prev"),
+    // Rename mode mappings
+    (Mode::Rename, "ctrl-c", "exit"),
+    (Mode::Rename, "esc", "cancel"),
+    (Mode::Rename, "enter", "confirm-rename"),
+    (Mode::Rename, "backspace", "backward-delete-char"),
+    (Mode::Rename, "ctrl-backspace", "clear-input"),
+    (Mode::Rename, "ctrl-u", "clear-input"),
+    // Help mode mappings
+    (Mode::Help, "esc", "cancel"),
+    (Mode::Help, "f1", "cancel"),
+    (Mode::Help, "ctrl-c", "exit"),
+    (Mode::Help, "down", "scroll-down"),
+    (Mode::Help, "ctrl-n", "scroll-down"),
+    (Mode::Help, "up", "scroll-up"),
+    (Mode::Help, "ctrl-p", "scroll-up"),
+    (Mode::Help, "backspace", "backward-delete-char"),
+];
 
-    mapping.bind(
-        Mode::Normal,
-        ctrl_n(),
-        Command::MoveSel(MoveDirection::Down),
-        "Select next",
-    );
-    mapping.bind(
-        Mode::Normal,
-        arrow_down(),
-        Command::MoveSel(MoveDirection::Down),
-        "Select next",
-    );
+// Additional key bindings when vi mode is enabled
+const VI_BINDINGS: [(Mode, &str, &str); 29] = [
+    (Mode::Normal, "esc", "vi-normal"),
+    (Mode::ViNormal, "ctrl-c", "exit"),
+    (Mode::ViNormal, "q", "exit"),
+    (Mode::ViNormal, "j", "select-next"),
+    (Mode::ViNormal, "down", "select-next"),
+    (Mode::ViNormal, "k", "select-prev"),
+    (Mode::ViNormal, "up", "select-prev"),
+    (Mode::ViNormal, "ctrl-f", "page-down"),
+    (Mode::ViNormal, "ctrl-b", "page-up"),
+    (Mode::ViNormal, "ctrl-d", "half-page-down"),
+    (Mode::ViNormal, "ctrl-u", "half-page-up"),
+    (Mode::ViNormal, "g g", "first"),
+    (Mode::ViNormal, "G", "last"),
+    (Mode::ViNormal, "d d", "delete"),
+    (Mode::ViNormal, "c w", "rename"),
+    (Mode::ViNormal, "/", "insert"),
+    (Mode::ViNormal, "i", "insert"),
+    (Mode::ViNormal, "enter", "default-action"),
+    (Mode::ViNormal, "o", "default-action"),
+    (Mode::ViNormal, "e", "edit"),
+    (Mode::ViNormal, "g x", "open"),
+    (Mode::ViNormal, "y y", "copy"),
+    (Mode::ViNormal, "tab", "action-menu"),
+    (Mode::ViNormal, "g s", "suggestions"),
+    (Mode::ViNormal, "f1", "help"),
+    (Mode::ActionMenu, "j", "select-next"),
+    (Mode::ActionMenu, "k", "select-prev"),
+    (Mode::Suggestions, "j", "select-next"),
+    (Mode::Suggestions, "k", "select-prev"),
+];
 
-    mapping.bind(
-        Mode::Normal,
-        ctrl_p(),
-        Command::MoveSel(MoveDirection::Up),
-        "Select previous",
-    );
-    mapping.bind(
-        Mode::Normal,
-        arrow_up(),
-        Command::MoveSel(MoveDirection::Up),
-        "Select previous",
-    );
+// Command name that removes a binding in the config
+const UNBIND: &str = "unbind";
 
-    mapping.bind(
-        Mode::Normal,
-        keys::enter(),
-        Command::DefaultAction,
-        "Run the default action",
-    );
+struct KeyBinding {
+    mode: Mode,
+    keys: Vec<KeySpec>,
+    command: Command,
+    desc: &'static str,
+}
 
-    mapping.bind(
-        Mode::Normal,
-        keys::ctrl_j(),
-        Command::EnterSelDir,
-        "Jump to the bookmarked directory",
-    );
+fn setup_keybindings(vi_mode: bool) -> Result<ModeMap<Command>> {
+    let vi_bindings: &[_] = if vi_mode { &VI_BINDINGS } else { &[] };
+    let mut bindings = Vec::new();
+    for &(mode, keys, name) in DEFAULT_BINDINGS.iter().chain(vi_bindings) {
+        let keys = keys::parse_sequence(keys).expect("Default key spec must be valid");
+        let (command, desc) = Command::from_name(name).expect("Default command must exist");
+        bindings.push(KeyBinding {
+            mode,
+            keys,
+            command,
+            desc,
+        });
+    }
+    let mut replaced = Vec::new();
+    for (idx, action) in config::get().actions.iter().enumerate() {
+        let key = match &action.key {
+            Some(key) => key,
+            None => continue,
+        };
+        let keys = keys::parse_sequence(key)
+            .map_err(|err| anyhow!("Invalid config key `actions.{}.key`: {}", action.name, err))?;
+        let modes: &[_] = if vi_mode {
+            &[Mode::Normal, Mode::ViNormal]
+        } else {
+            &[Mode::Normal]
+        };
+        for &mode in modes {
+            let origin = format!("actions.{}.key", action.name);
+            replace_binding(&mut bindings, &mut replaced, mode, &keys, &origin);
+            bindings.push(KeyBinding {
+                mode,
+                keys: keys.clone(),
+                command: Command::RunCustomAction(idx),
+                desc: action.desc(),
+            });
+        }
+    }
+    apply_config_bindings(&mut bindings, &mut replaced, &config::get().keys)?;
+    warn_unreachable_commands(&bindings, &replaced);
+    warn_shadowed_bindings(&bindings);
 
-    mapping.bind(
+    let mut mapping = ModeMap::new();
+    for binding in bindings {
+        match binding.keys.as_slice() {
+            [key] => mapping.bind(
+                binding.mode,
+                Combo::key(*key),
+                binding.command,
+                binding.desc,
+            ),
+            _ => mapping.bind_sequence(binding.mode, binding.keys, binding.command, binding.desc),
+        }
+    }
+    // Anything else typed goes to the input, or filters the key bindings in help
+    mapping.bind_with_input(
         Mode::Normal,
-        keys::ctrl_o(),
-        Command::OpenSelInEditor,
-        "Open the bookmarked location in $EDITOR",
+        keys::any_char(),
+        Command::InsertChar,
+        Some("Type the query".to_string()),
     );
-
-    mapping.bind(
-        Mode::Normal,
-        ctrl_k(),
-        Command::EnterMode(Mode::PendingDelete),
-        "Delete bookmark",
+    mapping.bind_with_input(
+        Mode::Rename,
+        keys::any_char(),
+        Command::InsertChar,
+        Some("Type the new name".to_string()),
     );
-    mapping.bind(
-        Mode::Normal,
-        ctrl_K(),
-        Command::EnterMode(Mode::PendingDelete),
-        "Delete bookmark",
+    mapping.bind_with_input(
+        Mode::Help,
+        keys::any_char(),
+        Command::InsertChar,
+        Some("Filter key bindings".to_string()),
     );
 
-    mapping.bind_with_desc(
-        Mode::Normal,
-        keys::backspace(),
-        Command::DeleteCharBack,
-        None,
-    );
+    Ok(mapping)
+}
 
-    mapping.bind(
-        Mode::Normal,
-        keys::ctrl_backspace(),
-        Command::ClearInput,
-        "Clear input",
-    );
+/// Override or unbind keys as specified in the `[keys.<mode>]` tables of the config.
+fn apply_config_bindings(
+    bindings: &mut Vec<KeyBinding>,
+    replaced: &mut Vec<(String, KeyBinding)>,
+    config_keys: &BTreeMap<String, BTreeMap<String, String>>,
+) -> Result<()> {
+    for (mode_name, mode_keys) in config_keys {
+        let mode = Mode::from_name(mode_name).with_context(|| {
+            let modes: Vec<&str> = Mode::ALL.into_iter().map(<&str>::from).collect();
+            format!(
+                "Invalid config key `keys.{}`: unknown mode, expected one of {}",
+                mode_name,
+                modes.join(", ")
+            )
+        })?;
 
-    mapping.bind_with_input(Mode::Normal, keys::any_char(), Command::InsertChar, None);
+        let mut configured: Vec<(Vec<KeySpec>, &str)> = Vec::new();
+        for (key_spec, name) in mode_keys {
+            let keys = keys::parse_sequence(key_spec).map_err(|err| {
+                anyhow!("Invalid config key `keys.{mode_name}.{key_spec}`: {err}")
+            })?;
+            if let Some((_, other_spec)) = configured.iter().find(|(other, _)| *other == keys) {
+                bail!(
+                    "Conflicting config keys `keys.{}.{}` and `keys.{}.{}`: both bind the same key",
+                    mode_name,
+                    other_spec,
+                    mode_name,
+                    key_spec
+                );
+            }
+            configured.push((keys.clone(), key_spec));
 
-    mapping.bind_with_desc(
-        Mode::Normal,
-        keys::f1(),
-        Command::EnterMode(Mode::Help),
-        None,
-    );
+            if name == UNBIND {
+                bindings.retain(|b| !(b.mode == mode && b.keys == keys));
+                continue;
+            }
+            let origin = format!("keys.{mode_name}.{key_spec}");
+            replace_binding(bindings, replaced, mode, &keys, &origin);
+            let (command, desc) = Command::from_name(name).with_context(|| {
+                let names: Vec<&str> = Command::names().collect();
+                format!(
+                    "Invalid config key `keys.{}.{}`: unknown command `{}`, expected `{}`, `{}<name>` of a custom action or one of {}",
+                    mode_name,
+                    key_spec,
+                    name,
+                    UNBIND,
+                    CUSTOM_ACTION_PREFIX,
+                    names.join(", ")
+                )
+            })?;
+            bindings.push(KeyBinding {
+                mode,
+                keys,
+                command,
+                desc,
+            });
+        }
+    }
 
-    // PendingDelete mode mappings
-    mapping.bind(
-        Mode::PendingDelete,
-        keys::ctrl_c(),
-        Command::ExitApp,
-        "Exit",
-    );
-    mapping.bind_with_desc(
-        Mode::PendingDelete,
-        keys::char('y'),
-        Command::DelSelBookmark,
-        None,
-    );
-    mapping.bind_with_desc(
-        Mode::PendingDelete,
-        keys::char('n'),
-        Command::EnterMode(Mode::Normal),
-        None,
-    );
+    Ok(())
+}
 
-    // Help mode mappings
-    mapping.bind_with_desc(
-        Mode::Help,
-        keys::esc(),
-        Command::EnterMode(Mode::Normal),
-        None,
-    );
-    mapping.bind(
-        Mode::Help,
-        keys::ctrl_c(),
-        Command::ExitApp,
-        "Exit application",
-    );
+// Replace the binding of the same keys, remembering it to report the conflict
+fn replace_binding(
+    bindings: &mut Vec<KeyBinding>,
+    replaced: &mut Vec<(String, KeyBinding)>,
+    mode: Mode,
+    keys: &[KeySpec],
+    origin: &str,
+) {
+    if let Some(idx) = bindings
+        .iter()
+        .position(|b| b.mode == mode && b.keys == keys)
+    {
+        replaced.push((origin.to_string(), bindings.remove(idx)));
+    }
+}
+
+// Taking over a default key is fine as long as its command can still be reached with other keys
+fn warn_unreachable_commands(bindings: &[KeyBinding], replaced: &[(String, KeyBinding)]) {
+    for (origin, old) in replaced {
+        let reachable = bindings
+            .iter()
+            .any(|b| b.mode == old.mode && b.command == old.command);
+        if !reachable {
+            warn!(
+                "Conflicting key bindings in {} mode: `{}` takes `{}` over from \"{}\", which isn't bound to any other key",
+                <&str>::from(old.mode),
+                origin,
+                keys::describe_sequence(&old.keys),
+                old.desc
+            );
+        }
+    }
+}
+
+// A binding can't be reached when its keys are the beginning of a longer sequence, and a character
+// can't be typed when a binding starts with it
+fn warn_shadowed_bindings(bindings: &[KeyBinding]) {
+    for binding in bindings {
+        let typed = matches!(binding.mode, Mode::Normal | Mode::Rename | Mode::Help)
+            && matches!(binding.keys[0], KeySpec { code: KeyCode::Char(_), modifiers } if modifiers.is_empty());
+        if typed {
+            warn!(
+                "Conflicting key bindings in {} mode: `{}` can't be typed anymore because of `{}`",
+                <&str>::from(binding.mode),
+                binding.keys[0],
+                keys::describe_sequence(&binding.keys)
+            );
+        }
 
-    mapping
+        let shadowing = bindings.iter().find(|other| {
+            other.mode == binding.mode
+                && other.keys.len() > binding.keys.len()
+                && other.keys.starts_with(&binding.keys)
+        });
+        if let Some(other) = shadowing {
+            warn!(
+                "Conflicting key bindings in {} mode: `{}` is shadowed by the sequence `{}`",
+                <&str>::from(binding.mode),
+                keys::describe_sequence(&binding.keys),
+                keys::describe_sequence(&other.keys)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(keys: &str, name: &str) -> KeyBinding {
+        let (command, desc) = Command::from_name(name).unwrap();
+        KeyBinding {
+            mode: Mode::Normal,
+            keys: keys::parse_sequence(keys).unwrap(),
+            command,
+            desc,
+        }
+    }
+
+    fn normal_keys(keys: &[(&str, &str)]) -> BTreeMap<String, BTreeMap<String, String>> {
+        let keys = keys
+            .iter()
+            .map(|(key, name)| (key.to_string(), name.to_string()))
+            .collect();
+        BTreeMap::from([("normal".to_string(), keys)])
+    }
+
+    #[test]
+    fn config_bindings_replace_and_unbind_defaults() {
+        let mut bindings = vec![
+            binding("ctrl-n", "select-next"),
+            binding("ctrl-k", "delete"),
+        ];
+        let mut replaced = vec![];
+        let config = normal_keys(&[("ctrl-n", "select-prev"), ("ctrl-k", "unbind")]);
+        apply_config_bindings(&mut bindings, &mut replaced, &config).unwrap();
+
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(
+            bindings[0].command,
+            Command::from_name("select-prev").unwrap().0
+        );
+        // Unbinding is deliberate, replacing is reported
+        assert_eq!(replaced.len(), 1);
+        assert_eq!(replaced[0].0, "keys.normal.ctrl-n");
+    }
+
+    #[test]
+    fn config_bindings_of_the_same_key_conflict() {
+        let config = normal_keys(&[("ctrl-n", "select-next"), ("C-n", "select-prev")]);
+        assert!(apply_config_bindings(&mut vec![], &mut vec![], &config).is_err());
+    }
+
+    #[test]
+    fn config_file_key_aliases_conflict() {
+        let config: config::Config = toml::from_str(
+            "[keys.normal]\n\"C-n\" = \"select-next\"\n\"ctrl-n\" = \"select-prev\"",
+        )
+        .unwrap();
+        let err = apply_config_bindings(&mut vec![], &mut vec![], &config.keys)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("`keys.normal.C-n` and `keys.normal.ctrl-n`"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn config_bindings_need_known_modes_commands_and_keys() {
+        let config = normal_keys(&[("ctrl-n", "no-such-command")]);
+        assert!(apply_config_bindings(&mut vec![], &mut vec![], &config).is_err());
+        let config = normal_keys(&[("hyper-n", "select-next")]);
+        assert!(apply_config_bindings(&mut vec![], &mut vec![], &config).is_err());
+        let config = BTreeMap::from([("insert".to_string(), BTreeMap::new())]);
+        assert!(apply_config_bindings(&mut vec![], &mut vec![], &config).is_err());
+    }
 }
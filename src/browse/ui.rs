@@ -4,12 +4,18 @@ use tui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Span, Spans},
-    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState},
+    widgets::{
+        Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, TableState,
+    },
     Frame, Terminal,
 };
 
 use super::*;
-use crate::{keys::ModeMap, storage::friendly_path};
+use crate::{
+    config,
+    keys::{self, ModeMap},
+    storage::friendly_path,
+};
 use std::{io::Stderr, iter::FromIterator};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -24,6 +30,55 @@ impl CursorLoc {
     }
 }
 
+fn main_block() -> Block<'static> {
+    Block::default().title("Shellmark").borders(Borders::ALL)
+}
+
+// Split the inside of the main block into input, list and bottom bar areas
+fn main_chunks(block_inner: Rect) -> Vec<Rect> {
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2),
+            Constraint::Min(1),
+            Constraint::Length(2),
+        ])
+        .margin(0)
+        .split(block_inner)
+}
+
+/// Area of the bookmark list in the terminal of the given size.
+pub fn list_area(size: Rect) -> Rect {
+    let chunks = main_chunks(main_block().inner(size));
+    Layout::default()
+        .horizontal_margin(1)
+        .constraints([Constraint::Percentage(100)])
+        .split(chunks[1])[0]
+}
+
+/// Number of bookmark rows that fit into the terminal of the given size.
+pub fn list_height(size: Rect) -> usize {
+    list_area(size).height as usize
+}
+
+// The help's header, filter, key bindings and bottom bar
+fn help_layout(outer: Rect) -> Vec<Rect> {
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(2),
+            Constraint::Min(1),
+            Constraint::Length(2),
+        ])
+        .split(outer)
+}
+
+/// Number of key binding lines of the help that fit into the terminal of the given size.
+pub fn help_height(size: Rect) -> usize {
+    help_layout(main_block().inner(size))[2].height as usize
+}
+
 pub fn draw_ui(
     terminal: &mut Terminal<CrosstermBackend<Stderr>>,
     new_state: &BrowseState,
@@ -34,19 +89,11 @@ pub fn draw_ui(
     terminal.hide_cursor()?;
     terminal.draw(|f| {
         let all_area = f.size();
-        let block = Block::default().title("Shellmark").borders(Borders::ALL);
+        let block = main_block();
         let block_inner = block.inner(all_area);
         f.render_widget(block, all_area);
 
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(2),
-                Constraint::Min(1),
-                Constraint::Length(2),
-            ])
-            .margin(0)
-            .split(block_inner);
+        let chunks = main_chunks(block_inner);
 
         let input_area = chunks[0];
         let input_block = Block::default().borders(Borders::BOTTOM);
@@ -71,12 +118,17 @@ pub fn draw_ui(
             input_block_area,
         );
 
-        let list_area = Layout::default()
-            .horizontal_margin(1)
-            .constraints([Constraint::Percentage(100)])
-            .split(chunks[1])[0];
-        let mut rows = Vec::with_capacity(new_state.selection.candidates.len());
-        for &sel_idx in &new_state.selection.candidates {
+        let list_area = list_area(all_area);
+        let theme = &config::get().theme;
+        let offset = new_state.selection.offset;
+        let visible = new_state
+            .selection
+            .candidates
+            .iter()
+            .skip(offset)
+            .take(list_area.height as usize);
+        let mut rows = Vec::with_capacity(list_area.height as usize);
+        for &sel_idx in visible {
             assert!(
                 sel_idx < new_state.bookmarks.len(),
                 "Selection index is out of range: {} ∉ ({}, {})",
@@ -85,15 +137,26 @@ pub fn draw_ui(
                 new_state.bookmarks.len()
             );
             // Render bookmark name with some colorization
-            let bm_name =
-                colorize_match(&new_state.bookmarks[sel_idx].name, &new_state.input.input);
-            let bm_name = Cell::from(bm_name).style(Style::default().fg(Color::Green));
+            let bm_name = colorize_match(
+                &new_state.bookmarks[sel_idx].name,
+                &new_state.input.input,
+                theme.matched.0,
+            );
+            let bm_name = Cell::from(bm_name).style(Style::default().fg(theme.name.0));
             // Render bookmark dest with some colorization
-            let bm_dest = colorize_match(
+            let mut bm_dest = colorize_match(
                 &friendly_path(&new_state.bookmarks[sel_idx].dest),
                 &new_state.input.input,
+                theme.matched.0,
             );
-            let bm_dest = Cell::from(bm_dest);
+            // Where the bookmark comes from when it's not the personal store
+            if let Some(label) = new_state.bookmarks[sel_idx].source.label() {
+                bm_dest.0.push(Span::styled(
+                    format!("  [{label}]"),
+                    Style::default().add_modifier(Modifier::DIM),
+                ));
+            }
+            let bm_dest = Cell::from(bm_dest).style(Style::default().fg(theme.dest.0));
             let row = Row::new(vec![bm_name, bm_dest]);
             rows.push(row);
         }
@@ -104,7 +167,12 @@ pub fn draw_ui(
             .highlight_symbol(">> ")
             .highlight_style(Style::default().add_modifier(Modifier::BOLD));
         let mut bookmarks_state = TableState::default();
-        bookmarks_state.select(new_state.selection.selected);
+        bookmarks_state.select(
+            new_state
+                .selection
+                .selected
+                .map(|selected| selected.saturating_sub(offset)),
+        );
 
         f.render_stateful_widget(bookmarks_tbl, list_area, &mut bookmarks_state);
 
@@ -115,47 +183,53 @@ pub fn draw_ui(
         f.render_widget(bottom_block, bottom_area);
 
         let key_style = Style::default().add_modifier(Modifier::BOLD);
-        let key_desk_style = Style::default().add_modifier(Modifier::ITALIC);
-        let help_text = Spans::from(vec![
-            Span::styled("[F1]", key_style),
-            Span::raw(" "),
-            Span::styled("Help", key_desk_style),
-            Span::raw(" "),
-            Span::styled("[Enter]", key_style),
-            Span::raw(" "),
-            Span::styled("DWIM", key_desk_style),
-            Span::raw(" "),
-            Span::styled("[C-j]", key_style),
-            Span::raw(" "),
-            Span::styled("Jump", key_desk_style),
-            Span::raw(" "),
-            Span::styled("[C-o]", key_style),
-            Span::raw(" "),
-            Span::styled("Edit", key_desk_style),
-        ]);
-
         f.render_widget(
-            Paragraph::new(help_text).alignment(Alignment::Left),
+            Paragraph::new(status_hints(keybinds, new_state.mode)).alignment(Alignment::Left),
             bottom_block_area,
         );
 
+        // Render the prefix of a key sequence that is being typed, or the vi mode indicator
+        let status_text = match &new_state.pending {
+            Some(pending) => Some(format!("{} -", keys::describe_sequence(&pending.keys))),
+            None if new_state.mode == Mode::ViNormal => Some("-- NORMAL --".to_string()),
+            None => None,
+        };
+        if let Some(status_text) = status_text {
+            f.render_widget(
+                Paragraph::new(Span::styled(status_text, key_style)).alignment(Alignment::Right),
+                bottom_block_area,
+            );
+        }
+
         // Render confirmation dialog for bookmark delete
         if new_state.mode == Mode::PendingDelete {
             render_confirm_delete_dialog(f, block_inner);
         }
 
+        if new_state.mode == Mode::ActionMenu {
+            render_action_menu(f, block_inner, &new_state.menu);
+        }
+
+        if new_state.mode == Mode::Suggestions {
+            render_suggestions(f, block_inner, &new_state.suggestions);
+        }
+
         if new_state.mode == Mode::Help {
-            render_help_window(f, block_inner, keybinds, Mode::Normal);
+            render_help_window(f, block_inner, keybinds, new_state);
         }
 
-        cursor_loc = CursorLoc::new(
-            input_block_area.x + new_state.input.cursor,
-            input_block_area.y,
-        );
+        cursor_loc = if new_state.mode == Mode::Rename {
+            render_rename_dialog(f, block_inner, new_state)
+        } else {
+            CursorLoc::new(
+                input_block_area.x + new_state.input.cursor,
+                input_block_area.y,
+            )
+        };
     })?;
 
     terminal.set_cursor(cursor_loc.x, cursor_loc.y)?;
-    if new_state.mode == Mode::Normal {
+    if matches!(new_state.mode, Mode::Normal | Mode::Rename) {
         terminal.show_cursor()?;
     } else {
         terminal.hide_cursor()?;
@@ -214,31 +288,239 @@ fn render_confirm_delete_dialog<B: Backend>(f: &mut Frame<B>, outer: Rect) {
     f.render_widget(content, dialog_chunk);
 }
 
+// Returns where the cursor goes in the name being typed
+fn render_rename_dialog<B: Backend>(
+    f: &mut Frame<B>,
+    outer: Rect,
+    state: &BrowseState,
+) -> CursorLoc {
+    let name = state.rename.to_string();
+    let taken = state
+        .selected_bookmark()
+        .is_some_and(|bm| state.is_name_taken(&bm, &name));
+    let status = if taken {
+        Span::styled(
+            "A bookmark with this name exists",
+            Style::default().fg(config::get().theme.matched.0),
+        )
+    } else {
+        Span::raw("")
+    };
+
+    let width = (outer.width.saturating_sub(4)).clamp(20, 60);
+    let area = Rect {
+        x: outer.x + outer.width.saturating_sub(width) / 2,
+        y: outer.y + outer.height.saturating_sub(4) / 2,
+        width: width.min(outer.width),
+        height: 4.min(outer.height),
+    };
+    let content = Paragraph::new(vec![Span::raw(name).into(), status.into()]).block(
+        Block::default()
+            .title("Rename bookmark")
+            .borders(Borders::ALL),
+    );
+
+    f.render_widget(Clear, area);
+    f.render_widget(content, area);
+    CursorLoc::new(
+        (area.x + 1 + state.rename.cursor).min(area.right().saturating_sub(2)),
+        area.y + 1,
+    )
+}
+
+// Hints like `[F1] Help` for the commands worth showing in the status bar
+fn status_hints(keybinds: &ModeMap<Command>, mode: Mode) -> Spans<'_> {
+    let mut hints: Vec<(&str, &str)> = vec![];
+    for binding in keybinds.descriptions(mode) {
+        let hint = binding.cmd.and_then(Command::hint);
+        if let Some(hint) = hint {
+            // Show only the first key of each hint
+            if !hints.iter().any(|(_, h)| *h == hint) {
+                hints.push((binding.keys, hint));
+            }
+        }
+    }
+    // Help always comes first
+    hints.sort_by_key(|(_, hint)| *hint != "Help");
+
+    let key_style = Style::default().add_modifier(Modifier::BOLD);
+    let key_desk_style = Style::default().add_modifier(Modifier::ITALIC);
+    let mut spans = vec![];
+    for (keys, hint) in hints {
+        if !spans.is_empty() {
+            spans.push(Span::raw(" "));
+        }
+        spans.push(Span::styled(format!("[{keys}]"), key_style));
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(hint, key_desk_style));
+    }
+    Spans::from(spans)
+}
+
+/// Lines of the help window: the bindings of the mode help was opened from, grouped by
+/// category and filtered by the help query.
+pub fn help_lines<'a>(keybinds: &'a ModeMap<Command>, state: &BrowseState) -> Vec<Spans<'a>> {
+    let query = String::from_iter(&state.help.query.input).to_lowercase();
+
+    // Merge the keys bound to the same command into one entry
+    let mut entries: Vec<(Category, Vec<&str>, &str, Option<&Command>)> = vec![];
+    for binding in keybinds.descriptions(state.base_mode) {
+        let existing = entries
+            .iter_mut()
+            .find(|(_, _, _, cmd)| cmd.is_some() && *cmd == binding.cmd);
+        match existing {
+            Some((_, keys, _, _)) => keys.push(binding.keys),
+            None => {
+                let category = binding.cmd.map_or(Category::Query, Command::category);
+                entries.push((category, vec![binding.keys], binding.desc, binding.cmd));
+            }
+        }
+    }
+    entries.retain(|(_, keys, desc, _)| {
+        desc.to_lowercase().contains(&query)
+            || keys.iter().any(|key| key.to_lowercase().contains(&query))
+    });
+    entries.sort_by_key(|(category, _, _, _)| *category);
+
+    let keys_width = entries
+        .iter()
+        .map(|(_, keys, _, _)| keys.join(", ").chars().count())
+        .max()
+        .unwrap_or(0);
+    let header_style = Style::default()
+        .add_modifier(Modifier::BOLD)
+        .add_modifier(Modifier::UNDERLINED);
+    let key_style = Style::default().add_modifier(Modifier::BOLD);
+
+    let mut lines = vec![];
+    let mut category = None;
+    for (entry_category, keys, desc, _) in entries {
+        if category != Some(entry_category) {
+            if category.is_some() {
+                lines.push(Spans::default());
+            }
+            lines.push(Spans::from(Span::styled(
+                entry_category.title(),
+                header_style,
+            )));
+            category = Some(entry_category);
+        }
+        lines.push(Spans::from(vec![
+            Span::styled(format!("  {:>keys_width$}", keys.join(", ")), key_style),
+            Span::raw("  "),
+            Span::raw(desc),
+        ]));
+    }
+    if lines.is_empty() {
+        lines.push(Spans::from("No matching key bindings"));
+    }
+
+    lines
+}
+
+fn render_action_menu<B: Backend>(f: &mut Frame<B>, outer: Rect, menu: &ActionMenu) {
+    let items: Vec<_> = menu
+        .items
+        .iter()
+        .map(|item| ListItem::new(item.label.as_str()))
+        .collect();
+    let width = menu
+        .items
+        .iter()
+        .map(|item| item.label.chars().count() as u16)
+        .max()
+        .unwrap_or(0)
+        + 7;
+    let height = menu.items.len() as u16 + 2;
+
+    let list = List::new(items)
+        .block(Block::default().title("Actions").borders(Borders::ALL))
+        .highlight_symbol(">> ")
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD));
+    let mut list_state = ListState::default();
+    list_state.select(Some(menu.selected));
+
+    let menu_area = Rect {
+        x: outer.x + outer.width.saturating_sub(width) / 2,
+        y: outer.y + outer.height.saturating_sub(height) / 2,
+        width: width.min(outer.width),
+        height: height.min(outer.height),
+    };
+
+    f.render_widget(Clear, menu_area);
+    f.render_stateful_widget(list, menu_area, &mut list_state);
+}
+
+fn render_suggestions<B: Backend>(f: &mut Frame<B>, outer: Rect, suggestions: &SuggestionList) {
+    let labels: Vec<_> = if suggestions.items.is_empty() {
+        vec!["No often visited directories to bookmark".to_string()]
+    } else {
+        suggestions
+            .items
+            .iter()
+            .map(|item| {
+                let taken = if item.name_taken {
+                    ", name is taken"
+                } else {
+                    ""
+                };
+                format!(
+                    "{}  {}  ({} visits{})",
+                    item.name,
+                    friendly_path(&item.dir),
+                    item.count,
+                    taken
+                )
+            })
+            .collect()
+    };
+    let width = labels
+        .iter()
+        .map(|label| label.chars().count() as u16)
+        .max()
+        .unwrap_or(0)
+        + 7;
+    let height = labels.len() as u16 + 2;
+
+    let items: Vec<_> = labels.into_iter().map(ListItem::new).collect();
+    let list = List::new(items)
+        .block(Block::default().title("Bookmark?").borders(Borders::ALL))
+        .highlight_symbol(">> ")
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD));
+    let mut list_state = ListState::default();
+    if !suggestions.items.is_empty() {
+        list_state.select(Some(suggestions.selected));
+    }
+
+    let area = Rect {
+        x: outer.x + outer.width.saturating_sub(width) / 2,
+        y: outer.y + outer.height.saturating_sub(height) / 2,
+        width: width.min(outer.width),
+        height: height.min(outer.height),
+    };
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut list_state);
+}
+
 fn render_help_window<B: Backend>(
     f: &mut Frame<B>,
     outer: Rect,
     keybinds: &ModeMap<Command>,
-    mode: Mode,
+    state: &BrowseState,
 ) {
     f.render_widget(Clear, outer);
 
     // Prepare basic layout
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(1),
-            Constraint::Length(1),
-            Constraint::Min(1),
-            Constraint::Length(2),
-        ])
-        .split(outer);
+    let chunks = help_layout(outer);
     let header_chunk = chunks[0];
+    let filter_chunk = chunks[1];
     let table_chunk = chunks[2];
     let bottom_chunk = chunks[3];
 
     // Render the header
     let header_text = Span::styled(
-        "Key bindings",
+        format!("Key bindings ({})", Into::<&str>::into(state.base_mode)),
         Style::default()
             .add_modifier(Modifier::BOLD)
             .add_modifier(Modifier::UNDERLINED),
@@ -249,53 +531,38 @@ fn render_help_window<B: Backend>(
 
     f.render_widget(header, header_chunk);
 
-    // Render the key bindings
-    let table_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)])
-        .split(table_chunk);
-    let table_left_chunk = table_chunks[0];
-    let table_right_chunk = table_chunks[1];
-
-    let mut table_left_lines = vec![];
-    let mut table_right_lines = vec![];
-    for (combo_desc, action_desc) in keybinds.descriptions(mode) {
-        let combo_line = Spans::from(vec![
-            Span::styled(combo_desc, Style::default().add_modifier(Modifier::BOLD)),
-            Span::from(": "),
-        ]);
-        table_left_lines.push(combo_line);
-
-        let action_line = Spans::from(action_desc);
-        table_right_lines.push(action_line);
-    }
-
-    let table_left = Paragraph::new(table_left_lines).alignment(Alignment::Right);
-    let table_right = Paragraph::new(table_right_lines).alignment(Alignment::Left);
+    // Render the filter
+    let filter_block = Block::default().borders(Borders::BOTTOM);
+    let filter_area = filter_block.inner(filter_chunk);
+    f.render_widget(filter_block, filter_chunk);
+    let filter_text = Spans::from(vec![
+        Span::raw(" / "),
+        Span::raw(String::from_iter(&state.help.query.input)),
+    ]);
+    f.render_widget(Paragraph::new(filter_text), filter_area);
 
-    f.render_widget(table_left, table_left_chunk);
-    f.render_widget(table_right, table_right_chunk);
+    // Render the key bindings
+    let table_area = Layout::default()
+        .horizontal_margin(1)
+        .constraints([Constraint::Percentage(100)])
+        .split(table_chunk)[0];
+    let table = Paragraph::new(help_lines(keybinds, state))
+        .alignment(Alignment::Left)
+        .scroll((state.help.scroll as u16, 0));
+    f.render_widget(table, table_area);
 
     // Render bottom bar
     let bottom_block = Block::default().borders(Borders::TOP);
     let bottom_block_area = bottom_block.inner(bottom_chunk);
     f.render_widget(bottom_block, bottom_chunk);
 
-    let key_style = Style::default().add_modifier(Modifier::BOLD);
-    let key_desk_style = Style::default().add_modifier(Modifier::ITALIC);
-    let help_text = Spans::from(vec![
-        Span::styled("[Esc]", key_style),
-        Span::raw(" "),
-        Span::styled("Exit", key_desk_style),
-    ]);
-
     f.render_widget(
-        Paragraph::new(help_text).alignment(Alignment::Left),
+        Paragraph::new(status_hints(keybinds, Mode::Help)).alignment(Alignment::Left),
         bottom_block_area,
     );
 }
 
-fn colorize_match(str: &str, input: &[char]) -> Spans<'static> {
+fn colorize_match(str: &str, input: &[char], match_color: Color) -> Spans<'static> {
     let mut spans = Vec::new();
     let mut cur_span: Option<(bool, Vec<char>)> = None;
     let mut match_idx = 0;
@@ -311,7 +578,7 @@ fn colorize_match(str: &str, input: &[char]) -> Spans<'static> {
                     if existing_span.0 {
                         existing_span.1.push(ch);
                     } else {
-                        spans.push(colorize_span(existing_span));
+                        spans.push(colorize_span(existing_span, match_color));
                         cur_span = Some((true, vec![ch]));
                     }
                 }
@@ -326,7 +593,7 @@ fn colorize_match(str: &str, input: &[char]) -> Spans<'static> {
                     if !existing_span.0 {
                         existing_span.1.push(ch);
                     } else {
-                        spans.push(colorize_span(existing_span));
+                        spans.push(colorize_span(existing_span, match_color));
                         cur_span = Some((false, vec![ch]));
                     }
                 }
@@ -335,17 +602,17 @@ fn colorize_match(str: &str, input: &[char]) -> Spans<'static> {
     }
 
     if let Some(span) = cur_span {
-        spans.push(colorize_span(&span));
+        spans.push(colorize_span(&span, match_color));
     }
 
     Spans::from(spans)
 }
 
-fn colorize_span(span: &(bool, Vec<char>)) -> Span<'static> {
+fn colorize_span(span: &(bool, Vec<char>), match_color: Color) -> Span<'static> {
     let (is_match, text) = span;
     let str = String::from_iter(text);
     if *is_match {
-        Span::styled(str, Style::default().fg(Color::Red))
+        Span::styled(str, Style::default().fg(match_color))
     } else {
         Span::raw(str)
     }
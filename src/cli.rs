@@ -1,12 +1,22 @@
+use crate::export::ExportFormat;
+use crate::import::ImportFormat;
 use crate::shell::OutputType;
+use crate::sync::Sidebar;
 use clap::{crate_version, Parser};
+use clap_complete::Shell;
+use std::str::FromStr;
 
 #[derive(Parser)]
-#[command(version = crate_version!())]
+#[command(
+    version = crate_version!(),
+    override_usage = "shellmark [OPTIONS] [QUERY]...\n       shellmark [OPTIONS] <COMMAND>"
+)]
 /// Cross-platform CLI bookmarks manager.
 pub struct Opts {
     #[command(subcommand)]
     pub command: Option<Command>,
+    /// Query to start browsing with
+    pub query: Vec<String>,
     #[arg(short = 'o', long = "out", value_enum, default_value_t = OutputType::Plain)]
     /// Output result as plain text or as eval-able command for one of the shells
     pub out_type: OutputType,
@@ -18,10 +28,26 @@ pub enum Command {
     Add(AddCmd),
     /// (default, alias: b) Interactively find and select bookmarks
     Browse(BrowseCmd),
+    /// Copy the destination of the best matching bookmark to the clipboard
+    Copy(CopyCmd),
     /// Output a command string to integrate shellmark into the shell
     Plug(PlugCmd),
     /// Print storage location and other diagnostics
     Diag(DiagCmd),
+    /// Inspect the configuration file
+    Config(ConfigCmd),
+    /// Output a completion script for the shell
+    Completions(CompletionsCmd),
+    /// Count a visit of the directory to rank bookmarks by (see `plug --track`)
+    Visit(VisitCmd),
+    /// List often visited directories that aren't bookmarked yet
+    Suggest(SuggestCmd),
+    /// Import bookmarks from another directory jumping tool
+    Import(ImportCmd),
+    /// Export bookmarks for use without shellmark
+    Export(ExportCmd),
+    /// Keep bookmarks and a file manager's sidebar in sync
+    Sync(SyncCmd),
 }
 
 #[derive(Parser)]
@@ -35,18 +61,208 @@ pub struct AddCmd {
     /// Name of the bookmark (default: the name of the destination)
     #[arg(short, long)]
     pub name: Option<String>,
+    #[arg(short, long)]
+    /// Add to the project's `.shellmark.json`, created in the current directory if there is none
+    pub project: bool,
 }
 
 #[derive(Parser, Default)]
 #[command(alias = "b")]
-pub struct BrowseCmd {}
+pub struct BrowseCmd {
+    #[arg(long)]
+    /// Wrap around when moving the selection past the first or the last bookmark
+    pub cycle: bool,
+    #[arg(long)]
+    /// Enable vi mode: Esc switches from typing the query to navigating with vi keys
+    pub vi: bool,
+    #[arg(long, value_name = "N|N%")]
+    /// Render the browser inline below the prompt using N lines or N% of the terminal height
+    pub height: Option<Height>,
+    #[arg(long)]
+    /// Output the quoted path of the selected bookmark to insert it into the command line
+    pub insert: bool,
+    #[arg(long, value_name = "CMD")]
+    /// Command to open bookmarks with instead of xdg-open, open or explorer.exe, quoted like in a shell
+    pub opener: Option<String>,
+    /// Query to start with, like a bookmark's name
+    pub query: Vec<String>,
+}
+
+#[derive(Parser)]
+pub struct CopyCmd {
+    #[arg(required = true)]
+    /// Query to find the bookmark by, like in the browser
+    pub query: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Height {
+    Lines(u16),
+    Percent(u16),
+}
+
+impl Height {
+    pub fn rows(&self, term_height: u16) -> u16 {
+        match *self {
+            Height::Lines(lines) => lines,
+            Height::Percent(percent) => (term_height as u32 * percent as u32 / 100) as u16,
+        }
+    }
+}
+
+impl FromStr for Height {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid height '{s}', expected N or N%");
+        match s.strip_suffix('%') {
+            Some(percent) => match percent.parse() {
+                Ok(percent @ 1..=100) => Ok(Height::Percent(percent)),
+                _ => Err(invalid()),
+            },
+            None => match s.parse() {
+                Ok(lines @ 1..) => Ok(Height::Lines(lines)),
+                _ => Err(invalid()),
+            },
+        }
+    }
+}
 
 #[derive(Parser)]
 pub struct PlugCmd {
     #[arg(short, long, default_value = "s")]
     /// Name of the shell alias
     pub name: String,
+    #[arg(long)]
+    /// Only output the commands that register bookmarks as zsh named directories
+    pub named_dirs: bool,
+    #[arg(long)]
+    /// Count every directory the shell changes to as a visit, to rank bookmarks by
+    pub track: bool,
 }
 
 #[derive(Parser)]
 pub struct DiagCmd {}
+
+#[derive(Parser)]
+pub struct VisitCmd {
+    /// Directory the shell changed to
+    pub dir: String,
+}
+
+#[derive(Parser)]
+pub struct SuggestCmd {
+    #[arg(short = 'n', long, default_value_t = 10)]
+    /// Maximum number of directories to list
+    pub limit: usize,
+}
+
+#[derive(Parser)]
+pub struct ImportCmd {
+    #[arg(long, value_enum)]
+    /// Tool to import the bookmarks of
+    pub from: ImportFormat,
+    /// File to read instead of the tool's default one
+    pub file: Option<String>,
+    #[arg(long, value_name = "N", default_value_t = 50)]
+    /// Import only the N highest ranked directories of autojump, z, fasd and zoxide, 0 for all
+    pub limit: usize,
+    #[arg(long, value_name = "SCORE")]
+    /// Skip the directories of autojump, z, fasd and zoxide ranked lower than this
+    pub min_score: Option<f64>,
+    #[arg(long)]
+    /// List the bookmarks that would be imported without adding them
+    pub dry_run: bool,
+}
+
+#[derive(Parser)]
+pub struct ExportCmd {
+    #[arg(long, value_enum)]
+    /// Format to export the bookmarks in
+    pub format: ExportFormat,
+    /// File to write instead of the standard output
+    pub file: Option<String>,
+    #[arg(long)]
+    /// Export the shared and project bookmarks too, not only the personal ones
+    pub all_sources: bool,
+}
+
+#[derive(Parser)]
+pub struct SyncCmd {
+    #[arg(long, value_enum)]
+    /// File manager to sync the bookmarks with
+    pub with: Sidebar,
+    /// File to sync with instead of the file manager's default one
+    pub file: Option<String>,
+    #[arg(long)]
+    /// Add the shared and project bookmarks to the sidebar too, not only the personal ones
+    pub all_sources: bool,
+}
+
+#[derive(Parser)]
+pub struct CompletionsCmd {
+    #[arg(value_enum, required_unless_present = "bookmark_names")]
+    /// Shell to complete for
+    pub shell: Option<Shell>,
+    #[arg(short, long, value_name = "NAME")]
+    /// Complete the shell alias with this name too
+    pub alias: Option<String>,
+    // Called back by the completion scripts, so it isn't worth showing in the help
+    #[arg(long, hide = true, conflicts_with_all = ["shell", "alias"])]
+    pub bookmark_names: bool,
+}
+
+#[derive(Parser)]
+pub struct ConfigCmd {
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+#[derive(Parser)]
+pub enum ConfigCommand {
+    /// Print the effective configuration as TOML
+    Show,
+    /// Print the location of the configuration file
+    Path,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn opts_are_consistent() {
+        Opts::command().debug_assert();
+    }
+
+    #[test]
+    fn query_is_told_from_subcommands() {
+        let opts = Opts::try_parse_from(["shellmark", "-o", "posix", "copy", "docs"]).unwrap();
+        assert!(matches!(opts.command, Some(Command::Copy(copy)) if copy.query == ["docs"]));
+        let opts = Opts::try_parse_from(["shellmark", "-o", "posix", "my", "docs"]).unwrap();
+        assert!(opts.command.is_none());
+        assert_eq!(opts.query, ["my", "docs"]);
+    }
+
+    #[test]
+    fn height_parses_lines_and_percent() {
+        assert_eq!("12".parse(), Ok(Height::Lines(12)));
+        assert_eq!("40%".parse(), Ok(Height::Percent(40)));
+        assert_eq!("100%".parse(), Ok(Height::Percent(100)));
+    }
+
+    #[test]
+    fn height_rejects_empty_and_out_of_range() {
+        for height in ["", "0", "-3", "0%", "101%", "%", "40 %", "ten"] {
+            assert!(height.parse::<Height>().is_err(), "{height}");
+        }
+    }
+
+    #[test]
+    fn height_percent_is_of_the_terminal() {
+        assert_eq!(Height::Percent(40).rows(50), 20);
+        assert_eq!(Height::Percent(100).rows(33), 33);
+        assert_eq!(Height::Lines(10).rows(50), 10);
+    }
+}
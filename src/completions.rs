@@ -0,0 +1,119 @@
+use std::iter;
+
+use anyhow::{bail, Result};
+use clap::{crate_version, CommandFactory};
+use clap_complete::Shell;
+
+use crate::{
+    bookmarks::read_bookmarks,
+    cli,
+    shell::{self, Output, OutputType},
+};
+
+// Name of the binary the completions are registered for
+const BIN_NAME: &str = "shellmark";
+
+// The PowerShell script registers its completer right away, so it's kept in a variable instead
+// to be called from the one that completes bookmark names
+const POWERSHELL_REGISTER: &str =
+    "Register-ArgumentCompleter -Native -CommandName 'shellmark' -ScriptBlock {";
+const POWERSHELL_KEEP: &str = "$__shellmarkClapCompleter = {";
+
+pub enum Completions {
+    Script(String),
+    BookmarkNames(Vec<String>),
+}
+
+impl Output for Completions {
+    fn to_output(&self, _out_type: OutputType) -> Option<String> {
+        match self {
+            Completions::Script(script) => Some(script.clone()),
+            Completions::BookmarkNames(names) => {
+                Some(names.iter().map(|name| format!("{name}\n")).collect())
+            }
+        }
+    }
+}
+
+pub async fn completions_cmd(opts: cli::CompletionsCmd) -> Result<Completions> {
+    if opts.bookmark_names {
+        let bookmarks = read_bookmarks().await?;
+        // A name is read back as one line
+        let names = bookmarks
+            .iter()
+            .filter(|bm| !bm.name.contains('\n'))
+            .map(|bm| bm.name.clone())
+            .collect();
+        return Ok(Completions::BookmarkNames(names));
+    }
+
+    let Some(shell) = opts.shell else {
+        bail!("The shell to complete for is missing");
+    };
+
+    // The scripts below complete the query themselves, while the generated ones take whatever is
+    // in place of the subcommand for the query
+    let full = cli::Opts::command();
+    let mut cmd = clap::Command::new(BIN_NAME)
+        .version(crate_version!())
+        .args(
+            full.get_arguments()
+                .filter(|arg| arg.get_id() != "query")
+                .cloned(),
+        )
+        .subcommands(full.get_subcommands().cloned());
+    let mut buf = vec![];
+    clap_complete::generate(shell, &mut cmd, BIN_NAME, &mut buf);
+    let mut script = String::from_utf8(buf)?;
+
+    let (bookmarks, out_type) = match shell {
+        Shell::Bash => (
+            include_str!("../integration/completions/complete.sh"),
+            OutputType::Posix,
+        ),
+        Shell::Zsh => (
+            include_str!("../integration/completions/complete.zsh"),
+            OutputType::Zsh,
+        ),
+        Shell::Fish => (
+            include_str!("../integration/completions/complete.fish"),
+            OutputType::Fish,
+        ),
+        Shell::Elvish => (
+            include_str!("../integration/completions/complete.elv"),
+            OutputType::Elvish,
+        ),
+        Shell::PowerShell => {
+            script = script.replacen(POWERSHELL_REGISTER, POWERSHELL_KEEP, 1);
+            (
+                include_str!("../integration/completions/complete.ps1"),
+                OutputType::PowerShell,
+            )
+        }
+        _ => return Ok(Completions::Script(script)),
+    };
+    let alias = match (&opts.alias, shell) {
+        (Some(alias), Shell::PowerShell) => format!(", {}", shell::quote(alias, out_type)),
+        (Some(alias), _) => shell::quote(alias, out_type),
+        (None, _) => String::new(),
+    };
+    script.push_str(
+        &bookmarks
+            .replace("{alias}", &alias)
+            .replace("{subcommands}", &subcommand_names()),
+    );
+
+    Ok(Completions::Script(script))
+}
+
+/// Names and aliases of the subcommands, which the scripts tell the browser's query from.
+pub fn subcommand_names() -> String {
+    let mut cmd = cli::Opts::command();
+    // With `help`
+    cmd.build();
+    let names: Vec<_> = cmd
+        .get_subcommands()
+        .flat_map(|sub| iter::once(sub.get_name()).chain(sub.get_all_aliases()))
+        .collect();
+    names.join(" ")
+}
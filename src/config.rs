@@ -0,0 +1,453 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsStr,
+    fmt::{Display, Formatter},
+    fs::Metadata,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tui::style::Color;
+
+use crate::{
+    cli::{self, ConfigCommand},
+    shell::{self, Output, OutputType},
+    storage::{self, expand_home},
+};
+
+const CONFIG_ENV_VAR: &str = "SHELLMARK_CONFIG";
+
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// What `Enter` does with the selected bookmark
+    pub default_action: DefaultAction,
+    /// Command used to open bookmarks in an editor instead of `$EDITOR`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub editor: Option<String>,
+    /// Where bookmarks and history are stored instead of the platform's data directory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<PathBuf>,
+    /// Read-only bookmark files shared with others, e.g. the `bookmarks.json` of a team's repository
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shared: Vec<PathBuf>,
+    /// How long to wait for the next key of a key sequence, in milliseconds
+    pub key_sequence_timeout: u64,
+    /// Navigate with vi keys after pressing Esc
+    pub vi_mode: bool,
+    pub matcher: MatcherConfig,
+    /// Order of bookmarks in the browser, matches with equal scores keep this order too
+    pub sort: SortOrder,
+    pub theme: Theme,
+    /// Extra actions for the browser that run a command on the selected bookmark
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<CustomAction>,
+    /// Key bindings per browser mode: key spec to command name
+    pub keys: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            default_action: DefaultAction::default(),
+            editor: None,
+            data_dir: None,
+            shared: vec![],
+            key_sequence_timeout: 1000,
+            vi_mode: false,
+            matcher: MatcherConfig::default(),
+            sort: SortOrder::default(),
+            theme: Theme::default(),
+            actions: vec![],
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DefaultAction {
+    /// Open files in the editor when it's set and jump to directories otherwise
+    #[default]
+    Auto,
+    Cd,
+    Edit,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct MatcherConfig {
+    pub kind: MatcherKind,
+    pub case: CaseMatching,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatcherKind {
+    #[default]
+    Fuzzy,
+    Substring,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaseMatching {
+    /// Case-sensitive only when the query contains uppercase letters
+    #[default]
+    Smart,
+    Ignore,
+    Respect,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortOrder {
+    /// The order in which bookmarks were added
+    #[default]
+    Added,
+    Name,
+    Path,
+    /// Most often and recently visited first
+    Frecent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Theme {
+    pub name: ThemeColor,
+    pub dest: ThemeColor,
+    #[serde(rename = "match")]
+    pub matched: ThemeColor,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            name: ThemeColor(Color::Green),
+            dest: ThemeColor(Color::Reset),
+            matched: ThemeColor(Color::Red),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CustomAction {
+    /// Name used to bind keys to the action as `action:<name>`
+    pub name: String,
+    /// Description for the action menu and the help, the name is used if it's missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desc: Option<String>,
+    /// Key that runs the action in the browser
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// Shell command with `{dest}`, `{dir}` and `{name}` placeholders
+    pub command: String,
+    #[serde(default)]
+    pub applies_to: AppliesTo,
+}
+
+impl CustomAction {
+    pub fn desc(&self) -> &str {
+        self.desc.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// Placeholders that can be used in the command of a custom action.
+pub const ACTION_PLACEHOLDERS: [&str; 3] = ["dest", "dir", "name"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AppliesTo {
+    #[default]
+    Any,
+    File,
+    Dir,
+}
+
+impl AppliesTo {
+    /// Whether an action applies to a destination with the given metadata, if it exists at all.
+    pub fn matches(&self, meta: Option<&Metadata>) -> bool {
+        match self {
+            AppliesTo::Any => true,
+            AppliesTo::File => meta.is_some_and(Metadata::is_file),
+            AppliesTo::Dir => meta.is_some_and(Metadata::is_dir),
+        }
+    }
+}
+
+/// A terminal color written as a name (`"green"`), a palette index (`"42"`) or RGB (`"#ff8800"`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ThemeColor(pub Color);
+
+const COLOR_NAMES: [(&str, Color); 17] = [
+    ("reset", Color::Reset),
+    ("black", Color::Black),
+    ("red", Color::Red),
+    ("green", Color::Green),
+    ("yellow", Color::Yellow),
+    ("blue", Color::Blue),
+    ("magenta", Color::Magenta),
+    ("cyan", Color::Cyan),
+    ("gray", Color::Gray),
+    ("dark-gray", Color::DarkGray),
+    ("light-red", Color::LightRed),
+    ("light-green", Color::LightGreen),
+    ("light-yellow", Color::LightYellow),
+    ("light-blue", Color::LightBlue),
+    ("light-magenta", Color::LightMagenta),
+    ("light-cyan", Color::LightCyan),
+    ("white", Color::White),
+];
+
+impl TryFrom<String> for ThemeColor {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let value = value.to_lowercase();
+        if let Some((_, color)) = COLOR_NAMES.iter().find(|(name, _)| *name == value) {
+            return Ok(ThemeColor(*color));
+        }
+        if let Ok(idx) = value.parse::<u8>() {
+            return Ok(ThemeColor(Color::Indexed(idx)));
+        }
+        if let Some(hex) = value.strip_prefix('#') {
+            if hex.len() == 6 {
+                if let Ok(rgb) = u32::from_str_radix(hex, 16) {
+                    return Ok(ThemeColor(Color::Rgb(
+                        (rgb >> 16) as u8,
+                        (rgb >> 8) as u8,
+                        rgb as u8,
+                    )));
+                }
+            }
+        }
+        let names: Vec<_> = COLOR_NAMES.iter().map(|(name, _)| *name).collect();
+        Err(format!(
+            "invalid color `{value}`, expected one of {}, a palette index or #rrggbb",
+            names.join(", ")
+        ))
+    }
+}
+
+impl From<ThemeColor> for String {
+    fn from(color: ThemeColor) -> Self {
+        match color.0 {
+            Color::Indexed(idx) => idx.to_string(),
+            Color::Rgb(r, g, b) => format!("#{r:02x}{g:02x}{b:02x}"),
+            color => COLOR_NAMES
+                .iter()
+                .find(|(_, c)| *c == color)
+                .map(|(name, _)| name.to_string())
+                .unwrap_or_else(|| "reset".to_string()),
+        }
+    }
+}
+
+/// Location of the config file: `$SHELLMARK_CONFIG` or `config.toml` next to the bookmarks in the
+/// platform's data dir. The config can move the bookmarks elsewhere, so it's never looked for there.
+pub fn config_path() -> PathBuf {
+    match env::var_os(CONFIG_ENV_VAR) {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ => storage::default_data_dir().join("config.toml"),
+    }
+}
+
+/// Read the config file and make it available via [`get`].
+///
+/// A missing config file at the default location means default settings, but a file explicitly
+/// set via `$SHELLMARK_CONFIG` has to exist.
+pub async fn load() -> Result<&'static Config> {
+    let explicit = env::var_os(CONFIG_ENV_VAR).is_some_and(|path| !path.is_empty());
+    let config = load_from(&config_path(), explicit).await?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// The loaded config, or the default one if nothing was loaded.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+async fn load_from(path: &Path, explicit: bool) -> Result<Config> {
+    if path.exists() {
+        read_config(path).await
+    } else if explicit {
+        bail!(
+            "Config file set via {} doesn't exist: {}",
+            CONFIG_ENV_VAR,
+            path.display()
+        );
+    } else {
+        Ok(Config::default())
+    }
+}
+
+async fn read_config(path: &Path) -> Result<Config> {
+    let content = fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Couldn't read config file: {}", path.display()))?;
+    let config: Config = toml::from_str(&content)
+        .with_context(|| format!("Invalid config file: {}", path.display()))?;
+    validate(config, path)
+}
+
+// Expand `~` in paths and check what deserializing can't
+fn validate(mut config: Config, path: &Path) -> Result<Config> {
+    config.data_dir = config.data_dir.map(|dir| expand_home(&dir));
+    config.shared = config.shared.iter().map(|file| expand_home(file)).collect();
+    for action in &config.actions {
+        let vars = ACTION_PLACEHOLDERS.map(|placeholder| (placeholder, OsStr::new("")));
+        shell::expand_template(&action.command, &vars, OutputType::Posix).map_err(|err| {
+            anyhow!(
+                "Invalid config key `actions.{}.command` in {}: {}",
+                action.name,
+                path.display(),
+                err
+            )
+        })?;
+    }
+    Ok(config)
+}
+
+pub enum ConfigOutput {
+    Show(String),
+    Path { path: PathBuf, exists: bool },
+}
+
+impl Display for ConfigOutput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigOutput::Show(content) => write!(f, "{content}"),
+            ConfigOutput::Path { path, exists } => {
+                write!(f, "{}", path.display())?;
+                if !exists {
+                    write!(f, " (doesn't exist, using defaults)")?;
+                }
+                writeln!(f)
+            }
+        }
+    }
+}
+
+impl Output for ConfigOutput {
+    fn to_output(&self, _out_type: OutputType) -> Option<String> {
+        Some(format!("{self}"))
+    }
+}
+
+pub fn config_cmd(opts: cli::ConfigCmd) -> Result<ConfigOutput> {
+    match opts.command {
+        ConfigCommand::Show => {
+            let content =
+                toml::to_string_pretty(get()).context("Couldn't serialize config to TOML")?;
+            Ok(ConfigOutput::Show(content))
+        }
+        ConfigCommand::Path => {
+            let path = config_path();
+            let exists = path.exists();
+            Ok(ConfigOutput::Path { path, exists })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Result<Config> {
+        let config = toml::from_str(content).map_err(|err| anyhow!("{err}"))?;
+        validate(config, Path::new("config.toml"))
+    }
+
+    fn error(content: &str) -> String {
+        format!("{:#}", parse(content).unwrap_err())
+    }
+
+    #[test]
+    fn config_keys_are_kebab_case_and_optional() {
+        let config = parse(
+            r#"
+            default-action = "edit"
+            data-dir = "~/bookmarks"
+            sort = "frecent"
+            key-sequence-timeout = 500
+
+            [matcher]
+            case = "ignore"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.default_action, DefaultAction::Edit);
+        assert_eq!(config.data_dir, Some(storage::home_dir().join("bookmarks")));
+        assert_eq!(config.sort, SortOrder::Frecent);
+        assert_eq!(config.key_sequence_timeout, 500);
+        assert_eq!(config.matcher.kind, MatcherKind::Fuzzy);
+        assert_eq!(config.matcher.case, CaseMatching::Ignore);
+        assert!(!config.vi_mode);
+    }
+
+    #[test]
+    fn unknown_config_keys_are_errors() {
+        assert!(error("vi_mode = true").contains("unknown field `vi_mode`"));
+        assert!(error("[matcher]\nkinds = \"fuzzy\"").contains("unknown field `kinds`"));
+        assert!(error("[theme]\nmatched = \"red\"").contains("unknown field `matched`"));
+        assert!(error("sort = \"random\"").contains("unknown variant `random`"));
+    }
+
+    #[test]
+    fn theme_colors_are_names_indices_or_rgb() {
+        let color = |value: &str| ThemeColor::try_from(value.to_string()).map(|color| color.0);
+        assert_eq!(color("Light-Blue"), Ok(Color::LightBlue));
+        assert_eq!(color("42"), Ok(Color::Indexed(42)));
+        assert_eq!(color("#FF8800"), Ok(Color::Rgb(0xff, 0x88, 0x00)));
+        for invalid in ["256", "#ff88", "#gg8800", "purple", ""] {
+            assert!(color(invalid).is_err(), "{invalid}");
+        }
+
+        let config = parse("[theme]\nname = \"#00ff00\"\nmatch = \"7\"").unwrap();
+        assert_eq!(config.theme.name.0, Color::Rgb(0, 0xff, 0));
+        assert_eq!(config.theme.matched.0, Color::Indexed(7));
+        assert_eq!(config.theme.dest.0, Color::Reset);
+        assert!(error("[theme]\nname = \"purple\"").contains("invalid color `purple`"));
+        // And written back the same way
+        assert_eq!(String::from(config.theme.name), "#00ff00");
+        assert_eq!(String::from(ThemeColor(Color::DarkGray)), "dark-gray");
+    }
+
+    #[test]
+    fn action_commands_only_use_known_placeholders() {
+        let action =
+            |command: &str| format!("[[actions]]\nname = \"code\"\ncommand = \"{command}\"");
+        let config = parse(&action("code {dest} {{literal}}")).unwrap();
+        assert_eq!(config.actions[0].desc(), "code");
+        assert_eq!(config.actions[0].applies_to, AppliesTo::Any);
+
+        let err = error(&action("code {dset}"));
+        assert!(
+            err.contains("`actions.code.command`") && err.contains("`{dset}`"),
+            "{err}"
+        );
+        assert!(error(&action("code {dest")).contains("unclosed"));
+        assert!(error("[[actions]]\nname = \"code\"").contains("missing field `command`"));
+    }
+
+    #[tokio::test]
+    async fn only_an_explicit_config_file_has_to_exist() {
+        let missing = env::temp_dir().join(format!("shellmark-no-config-{}", std::process::id()));
+        let err = load_from(&missing, true).await.unwrap_err().to_string();
+        assert!(
+            err.contains(CONFIG_ENV_VAR) && err.contains("doesn't exist"),
+            "{err}"
+        );
+
+        let config = load_from(&missing, false).await.unwrap();
+        assert_eq!(config.key_sequence_timeout, 1000);
+    }
+}
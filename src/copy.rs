@@ -0,0 +1,204 @@
+use std::{
+    env,
+    fs::OpenOptions,
+    io::{self, IsTerminal, Write},
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Result};
+use tracing::{info, warn};
+
+use crate::{
+    bookmarks::read_bookmarks,
+    cli, config,
+    search::{find_matches, Matcher},
+    storage::{friendly_path, simplify_path},
+    visits::read_visits,
+};
+
+pub async fn copy_cmd(opts: cli::CopyCmd) -> Result<()> {
+    let bookmarks = read_bookmarks().await?;
+    let config = config::get();
+    let visits = read_visits().await?;
+    let matcher = Matcher::from_config(&config.matcher);
+    let query = opts.query.join(" ");
+    let best_match = find_matches(&matcher, &bookmarks, config.sort, &visits, query.clone())
+        .first()
+        .map(|&idx| bookmarks[idx].clone());
+
+    match best_match {
+        Some(bm) => {
+            copy_to_clipboard(&simplify_path(&bm.dest).to_string_lossy())?;
+            info!(
+                "Copied the destination of {} to the clipboard: {}",
+                bm.name,
+                friendly_path(&bm.dest)
+            );
+            Ok(())
+        }
+        None => bail!("No bookmark matches `{}`", query),
+    }
+}
+
+/// Put the text on the clipboard of the terminal via OSC 52 and on the system clipboard.
+///
+/// OSC 52 also works over SSH, and in tmux and screen when it's passed through to the outer
+/// terminal. Not all terminals support it, so the clipboard tool of the desktop session is used
+/// as well when there is one.
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    let via_terminal = match write_osc52(text) {
+        Ok(written) => written,
+        Err(err) => {
+            warn!("Couldn't copy to the clipboard via the terminal: {}", err);
+            false
+        }
+    };
+    let via_tool = copy_with_tool(text);
+
+    if !via_terminal && !via_tool {
+        bail!("Couldn't copy to the clipboard: there is no terminal to send OSC 52 to and none of pbcopy, wl-copy or xclip is available");
+    }
+    Ok(())
+}
+
+// Returns whether there was a terminal to write the escape sequence to
+fn write_osc52(text: &str) -> io::Result<bool> {
+    let sequence = osc52_sequence(text, Multiplexer::detect());
+    // stdout is usually captured by the shell, so go to the terminal directly
+    if io::stderr().is_terminal() {
+        io::stderr().write_all(sequence.as_bytes())?;
+        return Ok(true);
+    }
+    match OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(mut tty) => {
+            tty.write_all(sequence.as_bytes())?;
+            Ok(true)
+        }
+        Err(_) => Ok(false),
+    }
+}
+
+/// Terminal multiplexers that swallow OSC 52 unless it's passed through to the outer terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Multiplexer {
+    Tmux,
+    Screen,
+}
+
+impl Multiplexer {
+    fn detect() -> Option<Multiplexer> {
+        if env::var_os("TMUX").is_some() {
+            Some(Multiplexer::Tmux)
+        } else if env::var_os("STY").is_some() {
+            Some(Multiplexer::Screen)
+        } else {
+            None
+        }
+    }
+}
+
+// Multiplexers pass a DCS sequence on as is, tmux 3.3+ only with `allow-passthrough` on
+fn osc52_sequence(text: &str, multiplexer: Option<Multiplexer>) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+    match multiplexer {
+        None => sequence,
+        Some(Multiplexer::Tmux) => {
+            format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+        }
+        Some(Multiplexer::Screen) => format!("\x1bP{}\x1b\\", sequence),
+    }
+}
+
+// Returns whether one of the clipboard tools took the text
+fn copy_with_tool(text: &str) -> bool {
+    let mut tools: Vec<(&str, &[&str])> = vec![];
+    if cfg!(target_os = "macos") {
+        tools.push(("pbcopy", &[]));
+    }
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        tools.push(("wl-copy", &[]));
+    }
+    if env::var_os("DISPLAY").is_some() {
+        tools.push(("xclip", &["-selection", "clipboard"]));
+    }
+
+    for (program, args) in tools {
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        // The tool isn't installed
+        let mut child = match child {
+            Ok(child) => child,
+            Err(_) => continue,
+        };
+        let written = match child.stdin.take() {
+            Some(mut stdin) => stdin.write_all(text.as_bytes()).is_ok(),
+            None => false,
+        };
+        if written && child.wait().is_ok_and(|status| status.success()) {
+            return true;
+        }
+    }
+
+    false
+}
+
+const BASE64_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_CHARS[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_matches_rfc_4648() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (input, encoded) in vectors {
+            assert_eq!(base64(input.as_bytes()), encoded, "{input}");
+        }
+        assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+    }
+
+    #[test]
+    fn osc52_is_passed_through_multiplexers() {
+        assert_eq!(osc52_sequence("foo", None), "\x1b]52;c;Zm9v\x07");
+        assert_eq!(
+            osc52_sequence("foo", Some(Multiplexer::Tmux)),
+            "\x1bPtmux;\x1b\x1b]52;c;Zm9v\x07\x1b\\"
+        );
+        assert_eq!(
+            osc52_sequence("foo", Some(Multiplexer::Screen)),
+            "\x1bP\x1b]52;c;Zm9v\x07\x1b\\"
+        );
+    }
+}
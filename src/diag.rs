@@ -1,4 +1,5 @@
 use crate::bookmarks;
+use crate::config;
 use crate::shell::{Output, OutputType};
 use crate::storage;
 use std::error::Error;
@@ -6,12 +7,14 @@ use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
 
 pub struct Diag {
+    pub config_file: PathBuf,
     pub data_dir: PathBuf,
     pub bookmark_count: usize,
 }
 
 impl Display for Diag {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Config file: {}", self.config_file.display())?;
         writeln!(f, "Data directory: {}", self.data_dir.display())?;
         writeln!(f, "Bookmark count: {}", self.bookmark_count)
     }
@@ -28,6 +31,7 @@ pub async fn diag_cmd() -> Result<Diag, Box<dyn Error>> {
     let bookmarks = bookmarks::read_bookmarks().await?;
     let bookmark_count = bookmarks.len();
     Ok(Diag {
+        config_file: config::config_path(),
         data_dir,
         bookmark_count,
     })
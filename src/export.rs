@@ -0,0 +1,374 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use tokio::fs;
+use tracing::{info, warn};
+
+use crate::{
+    bookmarks::{read_bookmarks, read_personal_bookmarks, Bookmark},
+    cli,
+    shell::{self, Output, OutputType},
+    storage::{friendly_path, simplify_path},
+};
+
+/// Formats to export bookmarks to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// The format bookmarks are stored in
+    Json,
+    /// `name,dest` rows with a header
+    Csv,
+    /// A `[[bookmarks]]` table per bookmark
+    Toml,
+    /// Shell aliases that change to the bookmarked directories, for the shell given with `--out`
+    Aliases,
+    /// The directories containing the bookmarks, to put into `CDPATH`
+    Cdpath,
+    /// A Markdown table
+    Markdown,
+}
+
+pub struct Export(String);
+
+impl Output for Export {
+    fn to_output(&self, _out_type: OutputType) -> Option<String> {
+        Some(self.0.clone())
+    }
+}
+
+pub async fn export_cmd(opts: cli::ExportCmd, out_type: OutputType) -> Result<Option<Export>> {
+    let bookmarks = match opts.all_sources {
+        true => read_bookmarks().await?,
+        false => read_personal_bookmarks().await?,
+    };
+    let content = match opts.format {
+        ExportFormat::Json => format!("{}\n", to_json(&bookmarks)?),
+        ExportFormat::Csv => to_csv(&bookmarks),
+        ExportFormat::Toml => to_toml(&bookmarks)?,
+        ExportFormat::Aliases => to_aliases(&bookmarks, out_type).await,
+        ExportFormat::Cdpath => to_cdpath(&bookmarks).await,
+        ExportFormat::Markdown => to_markdown(&bookmarks),
+    };
+
+    match opts.file {
+        Some(file) => {
+            fs::write(&file, content)
+                .await
+                .with_context(|| format!("Couldn't write {}", file))?;
+            info!("Exported {} bookmarks to {}", bookmarks.len(), file);
+            Ok(None)
+        }
+        None => Ok(Some(Export(content))),
+    }
+}
+
+fn plain(bookmarks: &[Arc<Bookmark>]) -> Vec<&Bookmark> {
+    bookmarks.iter().map(Arc::as_ref).collect()
+}
+
+fn to_json(bookmarks: &[Arc<Bookmark>]) -> Result<String> {
+    serde_json::to_string_pretty(&plain(bookmarks)).context("Couldn't serialize bookmarks to JSON")
+}
+
+fn to_toml(bookmarks: &[Arc<Bookmark>]) -> Result<String> {
+    #[derive(Serialize)]
+    struct Bookmarks<'a> {
+        bookmarks: Vec<&'a Bookmark>,
+    }
+
+    toml::to_string_pretty(&Bookmarks {
+        bookmarks: plain(bookmarks),
+    })
+    .context("Couldn't serialize bookmarks to TOML")
+}
+
+fn to_csv(bookmarks: &[Arc<Bookmark>]) -> String {
+    fn field(value: &str) -> String {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+
+    let mut out = String::from("name,dest\n");
+    for bm in bookmarks {
+        out.push_str(&format!(
+            "{},{}\n",
+            field(&bm.name),
+            field(&simplify_path(&bm.dest).to_string_lossy())
+        ));
+    }
+    out
+}
+
+fn to_markdown(bookmarks: &[Arc<Bookmark>]) -> String {
+    // A newline would end the row, and `|` the cell even in a code span
+    fn cell(value: &str) -> String {
+        value.replace('\n', " ").replace('|', "\\|")
+    }
+    fn text(value: &str) -> String {
+        let mut out = String::with_capacity(value.len());
+        for c in value.chars() {
+            if matches!(
+                c,
+                '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '~' | '#' | '!'
+            ) {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+        cell(&out)
+    }
+    // Nothing is escaped in a code span, so fence it with more backticks than it has in a row
+    fn code(value: &str) -> String {
+        let longest_run = value.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+        let fence = "`".repeat(longest_run + 1);
+        let pad = if value.starts_with('`') || value.ends_with('`') {
+            " "
+        } else {
+            ""
+        };
+        cell(&format!("{fence}{pad}{value}{pad}{fence}"))
+    }
+
+    let mut out = String::from("| Name | Destination |\n| --- | --- |\n");
+    for bm in bookmarks {
+        out.push_str(&format!(
+            "| {} | {} |\n",
+            text(&bm.name),
+            code(&friendly_path(&bm.dest))
+        ));
+    }
+    out
+}
+
+// Like jumping in the browser, a bookmarked file leads to its directory
+async fn jump_dir(dest: &Path) -> PathBuf {
+    let dest = simplify_path(dest);
+    match fs::metadata(dest).await {
+        Ok(meta) if meta.is_file() => dest.parent().unwrap_or(dest).to_path_buf(),
+        _ => dest.to_path_buf(),
+    }
+}
+
+// Names that are valid aliases or functions in all the shells
+fn is_alias_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+async fn to_aliases(bookmarks: &[Arc<Bookmark>], out_type: OutputType) -> String {
+    let mut out = String::new();
+    for bm in bookmarks {
+        if !is_alias_name(&bm.name) {
+            warn!("Skipped {}: the name can't be an alias", bm.name);
+            continue;
+        }
+        let dir = jump_dir(&bm.dest).await;
+        let name = &bm.name;
+        let line = match out_type {
+            OutputType::Plain | OutputType::Posix | OutputType::Zsh => {
+                let cd = format!("cd {}", shell::quote(&dir, out_type));
+                format!("alias {}={}", name, shell::quote(cd, out_type))
+            }
+            OutputType::Fish => {
+                let cd = format!("cd {}", shell::quote(&dir, out_type));
+                format!("alias {} {}", name, shell::quote(cd, out_type))
+            }
+            OutputType::PowerShell => format!(
+                "function {} {{ Set-Location -LiteralPath {} }}",
+                name,
+                shell::quote(&dir, out_type)
+            ),
+            OutputType::Nushell => format!("alias {} = cd {}", name, shell::quote(&dir, out_type)),
+            OutputType::Elvish => format!("fn {} {{ cd {} }}", name, shell::quote(&dir, out_type)),
+            OutputType::Xonsh => format!(
+                "aliases[{}] = ['cd', {}]",
+                shell::quote(name, out_type),
+                shell::quote(&dir, out_type)
+            ),
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+async fn to_cdpath(bookmarks: &[Arc<Bookmark>]) -> String {
+    // `cd <dir name>` finds a bookmarked directory when its parent is in CDPATH
+    let mut parents: Vec<PathBuf> = vec![];
+    for bm in bookmarks {
+        let dir = jump_dir(&bm.dest).await;
+        let parent = dir.parent().unwrap_or(&dir).to_path_buf();
+        if !parents.contains(&parent) {
+            parents.push(parent);
+        }
+    }
+
+    // The separator can't be a part of the paths
+    parents.retain(|parent| {
+        let valid = env::join_paths([parent]).is_ok();
+        if !valid {
+            warn!(
+                "Skipped {}: the path contains the separator",
+                friendly_path(parent)
+            );
+        }
+        valid
+    });
+    let joined = env::join_paths(parents).unwrap_or_default();
+    format!("{}\n", joined.to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    fn bookmarks(entries: &[(&str, &str)]) -> Vec<Arc<Bookmark>> {
+        entries
+            .iter()
+            .map(|(name, dest)| Arc::new(Bookmark::new(name.to_string(), PathBuf::from(dest))))
+            .collect()
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        let bookmarks = bookmarks(&[
+            ("src", "/srv/src"),
+            ("a, b", "/srv/a,b"),
+            ("say \"hi\"", "/srv/multi\nline"),
+        ]);
+        assert_eq!(
+            to_csv(&bookmarks),
+            "name,dest\n\
+             src,/srv/src\n\
+             \"a, b\",\"/srv/a,b\"\n\
+             \"say \"\"hi\"\"\",\"/srv/multi\nline\"\n"
+        );
+    }
+
+    #[test]
+    fn toml_reads_back_as_the_same_bookmarks() {
+        #[derive(Deserialize)]
+        struct Bookmarks {
+            bookmarks: Vec<Bookmark>,
+        }
+
+        let bookmarks = bookmarks(&[
+            ("src", "/srv/src"),
+            ("it's \"here\"", "/srv/it's\n\\here"),
+            ("[table]", "/srv/= #"),
+        ]);
+        let parsed: Bookmarks = toml::from_str(&to_toml(&bookmarks).unwrap()).unwrap();
+        let expected: Vec<_> = bookmarks.iter().map(|bm| bm.as_ref().clone()).collect();
+        assert_eq!(parsed.bookmarks, expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn markdown_cells_are_escaped() {
+        let bookmarks = bookmarks(&[
+            ("a|b", "/srv/a|b"),
+            ("[link](x) *bold*", "/srv/`tick`"),
+            ("multi\nline", "/srv/``two``"),
+        ]);
+        assert_eq!(
+            to_markdown(&bookmarks),
+            r"| Name | Destination |
+| --- | --- |
+| a\|b | `/srv/a\|b` |
+| \[link\](x) \*bold\* | `` /srv/`tick` `` |
+| multi line | ``` /srv/``two`` ``` |
+"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn aliases_are_quoted_for_each_shell() {
+        let bookmarks = bookmarks(&[
+            ("docs", "/srv/it's here"),
+            ("my docs", "/srv/docs"),
+            ("-rf", "/srv/rf"),
+            ("", "/srv/empty"),
+            ("ünï", "/srv/unicode"),
+        ]);
+        let cases = [
+            (
+                OutputType::Posix,
+                r"alias docs='cd '\''/srv/it'\''\'\'''\''s here'\'''",
+            ),
+            (OutputType::Fish, r"alias docs 'cd \'/srv/it\\\'s here\''"),
+            (
+                OutputType::PowerShell,
+                "function docs { Set-Location -LiteralPath '/srv/it''s here' }",
+            ),
+            (OutputType::Nushell, r#"alias docs = cd "/srv/it's here""#),
+            (OutputType::Elvish, "fn docs { cd '/srv/it''s here' }"),
+            (
+                OutputType::Xonsh,
+                r"aliases['docs'] = ['cd', '/srv/it\'s here']",
+            ),
+        ];
+        for (out_type, alias) in cases {
+            // Only `docs` makes a valid alias name in every shell
+            assert_eq!(
+                to_aliases(&bookmarks, out_type).await,
+                format!("{alias}\n"),
+                "{out_type:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn cdpath_joins_the_parents_with_the_platform_separator() {
+        let root = env::temp_dir().join(format!("shellmark-cdpath-{}", std::process::id()));
+        let src = root.join("src");
+        let notes = root.join("notes.txt");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::write(&notes, "").unwrap();
+        let dests = [
+            src,
+            root.join("docs"),
+            notes,
+            root.join("other").join("dir"),
+        ];
+        let bookmarks: Vec<_> = dests
+            .iter()
+            .map(|dest| Arc::new(Bookmark::new("bm".to_string(), dest.clone())))
+            .collect();
+
+        // A bookmarked file leads to its directory, whose parent is in CDPATH
+        let parents = [
+            root.clone(),
+            root.parent().unwrap().to_path_buf(),
+            root.join("other"),
+        ];
+        let expected = env::join_paths(parents).unwrap();
+        assert_eq!(
+            to_cdpath(&bookmarks).await,
+            format!("{}\n", expected.to_string_lossy())
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn cdpath_skips_paths_containing_the_separator() {
+        let bookmarks = bookmarks(&[("a", "/srv/a:b/dir"), ("b", "/srv/b/dir")]);
+        assert_eq!(to_cdpath(&bookmarks).await, "/srv/b\n");
+    }
+}
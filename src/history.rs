@@ -0,0 +1,119 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use crate::storage;
+
+// Keep the history file small, older queries are dropped first
+const HISTORY_LIMIT: usize = 1000;
+
+fn history_file(data_dir: &Path) -> PathBuf {
+    data_dir.join("history.json")
+}
+
+/// Read the queries that led to an accepted action, oldest first.
+pub async fn read_history() -> Result<Vec<String>> {
+    let data_dir = storage::get_or_create_data_dir().await?;
+    read_history_intern(&history_file(&data_dir)).await
+}
+
+/// Record a query as the most recent history entry.
+///
+/// A query that is already present in the history is moved to the end instead of being duplicated.
+pub async fn append_history(query: &str) -> Result<()> {
+    let data_dir = storage::get_or_create_data_dir().await?;
+    append_history_intern(&history_file(&data_dir), query).await
+}
+
+async fn append_history_intern(history_file: &Path, query: &str) -> Result<()> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(());
+    }
+
+    let mut history = read_history_intern(history_file).await?;
+    history.retain(|q| q != query);
+    history.push(query.to_string());
+    if history.len() > HISTORY_LIMIT {
+        history.drain(..history.len() - HISTORY_LIMIT);
+    }
+
+    write_history_intern(history_file, &history).await
+}
+
+async fn read_history_intern(history_file: &Path) -> Result<Vec<String>> {
+    if !history_file.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(history_file)
+        .await
+        .with_context(|| format!("Couldn't read history file: {}", history_file.display()))?;
+
+    if content.trim().is_empty() {
+        Ok(Vec::new())
+    } else {
+        serde_json::from_str(&content).context("Couldn't parse history JSON")
+    }
+}
+
+async fn write_history_intern(history_file: &Path, history: &[String]) -> Result<()> {
+    let content =
+        serde_json::to_string_pretty(history).context("Couldn't serialize history to JSON")?;
+    Ok(fs::write(history_file, content).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn queries_are_deduplicated_and_trimmed() {
+        let dir = env::temp_dir().join(format!("shellmark-history-{}", process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let history_file = history_file(&dir);
+
+        for query in ["docs", " src ", "", "   ", "docs", "notes"] {
+            append_history_intern(&history_file, query).await.unwrap();
+        }
+        // A repeated query moves to the end, blank ones aren't recorded
+        assert_eq!(
+            read_history_intern(&history_file).await.unwrap(),
+            ["src", "docs", "notes"]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn the_oldest_queries_are_dropped_over_the_limit() {
+        let dir = env::temp_dir().join(format!("shellmark-history-limit-{}", process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let history_file = history_file(&dir);
+        let history: Vec<_> = (0..HISTORY_LIMIT).map(|n| format!("q{n}")).collect();
+        write_history_intern(&history_file, &history).await.unwrap();
+
+        append_history_intern(&history_file, "new").await.unwrap();
+        let history = read_history_intern(&history_file).await.unwrap();
+        assert_eq!(history.len(), HISTORY_LIMIT);
+        assert_eq!(history.first().unwrap(), "q1");
+        assert_eq!(history.last().unwrap(), "new");
+
+        // Moving a query that is already there to the end doesn't drop anything
+        append_history_intern(&history_file, "q1").await.unwrap();
+        let history = read_history_intern(&history_file).await.unwrap();
+        assert_eq!(history.len(), HISTORY_LIMIT);
+        assert_eq!(history[..2], ["q2", "q3"]);
+        assert_eq!(history.last().unwrap(), "q1");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_missing_history_file_is_an_empty_history() {
+        let missing = env::temp_dir().join(format!("shellmark-no-history-{}", process::id()));
+        assert!(read_history_intern(&missing).await.unwrap().is_empty());
+    }
+}
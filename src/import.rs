@@ -0,0 +1,682 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use directories::BaseDirs;
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use tokio::fs;
+use tracing::{info, warn};
+
+use crate::{
+    add::default_name,
+    bookmarks::{read_bookmarks, write_bookmarks, Bookmark},
+    cli,
+    storage::{expand_home, friendly_path, home_dir},
+};
+
+/// Tools to import bookmarks from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    /// autojump's database, `weight<TAB>path` lines
+    Autojump,
+    /// z's `~/.z`, `path|rank|time` lines
+    Z,
+    /// Output of `zoxide query -ls`, or zoxide's `db.zo`
+    Zoxide,
+    /// fasd's `~/.fasd`, `path|rank|time` lines
+    Fasd,
+    /// bashmarks' `~/.sdirs`, `export DIR_name="path"` lines
+    Bashmarks,
+    /// wd's `~/.warprc`, `name:path` lines
+    Wd,
+    /// apparix's `~/.apparixrc`, `j,name,path` lines
+    Apparix,
+    /// A list of directories, one per line, like the output of fish's `dirh`
+    Dirh,
+    /// GTK file managers' `~/.config/gtk-3.0/bookmarks`, `file://` URIs with optional labels
+    Gtk,
+    /// KDE's places, `~/.local/share/user-places.xbel`
+    Xbel,
+}
+
+impl ImportFormat {
+    // The tools that record every directory visited, ranked by how often and how recently
+    fn is_ranked(self) -> bool {
+        matches!(
+            self,
+            ImportFormat::Autojump | ImportFormat::Z | ImportFormat::Fasd | ImportFormat::Zoxide
+        )
+    }
+}
+
+// A bookmark to be, the name is derived from the destination when the tool has none
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub name: Option<String>,
+    pub dest: PathBuf,
+    // The tool's rank of the directory, if it ranks them
+    pub score: Option<f64>,
+}
+
+/// How many entries were turned into bookmarks and why the others weren't.
+#[derive(Debug, Default)]
+pub struct Merged {
+    pub imported: usize,
+    pub present: usize,
+    pub missing: usize,
+    pub conflicts: usize,
+}
+
+impl Merged {
+    pub fn report(&self, dry_run: bool) {
+        info!(
+            "{} {} bookmarks, {} were already present, {} don't exist anymore and {} have conflicting names",
+            if dry_run { "Would import" } else { "Imported" },
+            self.imported, self.present, self.missing, self.conflicts
+        );
+        if self.conflicts > 0 {
+            info!("Consider adding the conflicting ones under other names with `shellmark add --name`");
+        }
+    }
+}
+
+pub async fn import_cmd(opts: cli::ImportCmd) -> Result<()> {
+    let mut entries = read_entries(opts.from, opts.file.as_deref()).await?;
+    if opts.from.is_ranked() {
+        let total = entries.len();
+        entries = top_entries(entries, opts.min_score, opts.limit);
+        if entries.len() < total {
+            info!(
+                "Importing the {} highest ranked of {} directories, see --limit and --min-score",
+                entries.len(),
+                total
+            );
+        }
+    }
+    let mut bookmarks = read_bookmarks().await?;
+
+    let existing = bookmarks.len();
+    let merged = merge_entries(&mut bookmarks, entries).await;
+    if opts.dry_run {
+        for bm in &bookmarks[existing..] {
+            info!("Would import {}: {}", bm.name, friendly_path(&bm.dest));
+        }
+    } else if merged.imported > 0 {
+        write_bookmarks(&bookmarks).await?;
+    }
+    merged.report(opts.dry_run);
+
+    Ok(())
+}
+
+// The highest ranked entries first, a limit of 0 keeps all of them
+fn top_entries(mut entries: Vec<Entry>, min_score: Option<f64>, limit: usize) -> Vec<Entry> {
+    let score = |entry: &Entry| entry.score.unwrap_or_default();
+    if let Some(min_score) = min_score {
+        entries.retain(|entry| score(entry) >= min_score);
+    }
+    entries.sort_by(|a, b| score(b).total_cmp(&score(a)));
+    if limit > 0 {
+        entries.truncate(limit);
+    }
+    entries
+}
+
+/// Add the entries as bookmarks, existing bookmarks are never overwritten.
+pub async fn merge_entries(bookmarks: &mut Vec<Arc<Bookmark>>, entries: Vec<Entry>) -> Merged {
+    let mut merged = Merged::default();
+    for entry in entries {
+        let dest = match fs::canonicalize(&entry.dest).await {
+            Ok(dest) => dest,
+            Err(_) => {
+                warn!(
+                    "Skipped {}: the destination doesn't exist",
+                    friendly_path(&entry.dest)
+                );
+                merged.missing += 1;
+                continue;
+            }
+        };
+        // Without a name from the tool, a bookmark of the same destination is as good
+        if entry.name.is_none() && bookmarks.iter().any(|bm| bm.dest == dest) {
+            merged.present += 1;
+            continue;
+        }
+        let name = entry.name.unwrap_or_else(|| default_name(&dest));
+
+        match bookmarks.iter().find(|bm| bm.name == name) {
+            Some(existing) if existing.dest == dest => merged.present += 1,
+            Some(existing) => {
+                warn!(
+                    "Skipped {} pointing at {}: a bookmark with this name points at {}",
+                    name,
+                    friendly_path(&dest),
+                    friendly_path(&existing.dest)
+                );
+                merged.conflicts += 1;
+            }
+            None => {
+                bookmarks.push(Arc::new(Bookmark::new(name, dest)));
+                merged.imported += 1;
+            }
+        }
+    }
+    merged
+}
+
+async fn read_entries(format: ImportFormat, file: Option<&str>) -> Result<Vec<Entry>> {
+    let file = match file {
+        Some(file) => PathBuf::from(file),
+        // zoxide's database is internal, so ask zoxide itself
+        None if format == ImportFormat::Zoxide => {
+            let output = Command::new("zoxide")
+                .args(["query", "--list", "--score"])
+                .output()
+                .context("Couldn't run `zoxide query --list --score`, is zoxide installed?")?;
+            if !output.status.success() {
+                bail!("`zoxide query --list --score` failed");
+            }
+            return Ok(parse_scored_list(&String::from_utf8_lossy(&output.stdout)));
+        }
+        None => default_file(format)?,
+    };
+
+    let content = fs::read(&file)
+        .await
+        .with_context(|| format!("Couldn't read {}", friendly_path(&file)))?;
+    if format == ImportFormat::Zoxide && content.starts_with(&ZOXIDE_DB_VERSION.to_le_bytes()) {
+        return parse_zoxide_db(&content).context("Couldn't parse zoxide's database");
+    }
+    parse_entries(format, &String::from_utf8_lossy(&content))
+}
+
+/// Read the entries of a tool's text file.
+pub fn parse_entries(format: ImportFormat, content: &str) -> Result<Vec<Entry>> {
+    let entries = match format {
+        ImportFormat::Autojump => parse_autojump(content),
+        ImportFormat::Z | ImportFormat::Fasd => parse_z(content),
+        ImportFormat::Zoxide => parse_scored_list(content),
+        ImportFormat::Bashmarks => parse_bashmarks(content),
+        ImportFormat::Wd => parse_wd(content),
+        ImportFormat::Apparix => parse_apparix(content),
+        ImportFormat::Dirh => parse_dirh(content),
+        ImportFormat::Gtk => parse_gtk(content),
+        ImportFormat::Xbel => parse_xbel(content).context("Couldn't parse the XBEL file")?,
+    };
+    Ok(entries)
+}
+
+/// Where the tool keeps its bookmarks, honoring the variables that move them.
+pub fn default_file(format: ImportFormat) -> Result<PathBuf> {
+    let from_env = |var: &str, default: &str| match env::var_os(var) {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ => home_dir().join(default),
+    };
+    let file = match format {
+        ImportFormat::Autojump => {
+            let data_dir = if cfg!(target_os = "macos") {
+                home_dir().join("Library")
+            } else {
+                base_dirs()?.data_local_dir().to_path_buf()
+            };
+            data_dir.join("autojump").join("autojump.txt")
+        }
+        ImportFormat::Z => from_env("_Z_DATA", ".z"),
+        ImportFormat::Fasd => from_env("_FASD_DATA", ".fasd"),
+        ImportFormat::Bashmarks => from_env("SDIRS", ".sdirs"),
+        ImportFormat::Wd => from_env("WD_CONFIG", ".warprc"),
+        ImportFormat::Apparix => home_dir().join(".apparixrc"),
+        ImportFormat::Gtk => base_dirs()?.config_dir().join("gtk-3.0").join("bookmarks"),
+        ImportFormat::Xbel => base_dirs()?.data_dir().join("user-places.xbel"),
+        ImportFormat::Zoxide | ImportFormat::Dirh => {
+            bail!("There is no default file to import from, please pass one")
+        }
+    };
+    Ok(file)
+}
+
+fn base_dirs() -> Result<BaseDirs> {
+    BaseDirs::new().context("Couldn't locate the config and data directories")
+}
+
+fn entry(name: Option<&str>, dest: &str) -> Option<Entry> {
+    ranked_entry(name, dest, None)
+}
+
+fn ranked_entry(name: Option<&str>, dest: &str, score: Option<f64>) -> Option<Entry> {
+    let dest = dest.trim();
+    if dest.is_empty() {
+        return None;
+    }
+    // Shells expand these before the tools see the paths, but some keep them in the files
+    let dest = match dest.strip_prefix("$HOME") {
+        Some(rest) => format!("~{rest}"),
+        None => dest.to_string(),
+    };
+    Some(Entry {
+        name: name.map(str::to_string).filter(|name| !name.is_empty()),
+        dest: expand_home(Path::new(&dest)),
+        score,
+    })
+}
+
+fn parse_autojump(content: &str) -> Vec<Entry> {
+    content
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .filter_map(|(weight, path)| ranked_entry(None, path, weight.trim().parse().ok()))
+        .collect()
+}
+
+fn parse_z(content: &str) -> Vec<Entry> {
+    content
+        .lines()
+        // Paths can contain `|`, the rank and the time can't
+        .filter_map(|line| {
+            let mut fields = line.rsplitn(3, '|').skip(1);
+            let rank = fields.next()?;
+            ranked_entry(None, fields.next()?, rank.trim().parse().ok())
+        })
+        .collect()
+}
+
+// `<score> <path>` lines, as printed by `zoxide query -ls`
+fn parse_scored_list(content: &str) -> Vec<Entry> {
+    content
+        .lines()
+        .filter_map(|line| line.trim_start().split_once(' '))
+        .filter_map(|(score, path)| ranked_entry(None, path, score.parse().ok()))
+        .collect()
+}
+
+fn parse_bashmarks(content: &str) -> Vec<Entry> {
+    content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("export DIR_"))
+        .filter_map(|line| line.split_once('='))
+        .filter_map(|(name, path)| entry(Some(name), path.trim_matches('"')))
+        .collect()
+}
+
+fn parse_wd(content: &str) -> Vec<Entry> {
+    content
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter_map(|(name, path)| entry(Some(name.trim()), path))
+        .collect()
+}
+
+fn parse_apparix(content: &str) -> Vec<Entry> {
+    content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("j,"))
+        .filter_map(|line| line.split_once(','))
+        .filter_map(|(name, path)| entry(Some(name), path))
+        .collect()
+}
+
+// Plain paths, or `dirh` lines like ` 2) /some/dir`
+fn parse_dirh(content: &str) -> Vec<Entry> {
+    content
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            match line.split_once(") ") {
+                Some((idx, path)) if idx.chars().all(|c| c.is_ascii_digit()) => path,
+                _ => line,
+            }
+        })
+        .filter_map(|path| entry(None, path))
+        .collect()
+}
+
+// `file:///some/dir Label` lines, other kinds of URIs are remote places
+fn parse_gtk(content: &str) -> Vec<Entry> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (uri, label) = match line.trim().split_once(' ') {
+                Some((uri, label)) => (uri, Some(label.trim())),
+                None => (line.trim(), None),
+            };
+            place_entry(uri, label)
+        })
+        .collect()
+}
+
+// The `href` of each `<bookmark>` and its `<title>`, wherever it is in the folders
+fn parse_xbel(content: &str) -> Result<Vec<Entry>> {
+    let mut reader = Reader::from_str(content);
+    let mut entries = vec![];
+    // The place being read: its href, its title and whether it's one of KDE's own
+    let mut place: Option<(String, Option<String>, bool)> = None;
+    // The innermost element the text is in
+    let mut element = vec![];
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.local_name().as_ref() == b"bookmark" => {
+                place = Some((href(&e)?.unwrap_or_default(), None, false));
+            }
+            Event::Empty(e) if e.local_name().as_ref() == b"bookmark" => {
+                entries.extend(place_entry(&href(&e)?.unwrap_or_default(), None));
+            }
+            Event::Start(e) => element = e.local_name().as_ref().to_vec(),
+            Event::Text(text) => match (&mut place, element.as_slice()) {
+                (Some((_, title, _)), b"title") => *title = Some(text.unescape()?.into_owned()),
+                // Home, Root and the like are there out of the box
+                (Some((_, _, system)), b"isSystemItem") => *system = &*text == b"true",
+                _ => {}
+            },
+            Event::End(e) => {
+                if e.local_name().as_ref() == b"bookmark" {
+                    if let Some((href, title, false)) = place.take() {
+                        entries.extend(place_entry(&href, title.as_deref()));
+                    }
+                }
+                element.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
+/// The `href` of an XBEL element.
+pub fn href(element: &BytesStart) -> Result<Option<String>> {
+    Ok(match element.try_get_attribute("href")? {
+        Some(href) => Some(href.unescape_value()?.into_owned()),
+        None => None,
+    })
+}
+
+fn place_entry(uri: &str, label: Option<&str>) -> Option<Entry> {
+    entry(label, &file_uri_path(uri)?)
+}
+
+/// Destination of a file manager's place as it's imported, `None` for remote places.
+pub fn place_dest(uri: &str) -> Option<PathBuf> {
+    place_entry(uri, None).map(|entry| entry.dest)
+}
+
+/// Path of a local `file://` URI.
+pub fn file_uri_path(uri: &str) -> Option<String> {
+    let path = uri.strip_prefix("file://")?;
+    let path = path.strip_prefix("localhost").unwrap_or(path);
+    if !path.starts_with('/') {
+        return None;
+    }
+
+    let mut bytes = vec![];
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    let path = String::from_utf8_lossy(&bytes).into_owned();
+    // `file:///C:/dir` on Windows
+    match path.get(2..3) {
+        Some(":") if cfg!(windows) => Some(path[1..].to_string()),
+        _ => Some(path),
+    }
+}
+
+/// `file://` URI of a path, as file managers write them.
+pub fn file_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from(if path.starts_with('/') {
+        "file://"
+    } else {
+        "file:///"
+    });
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~:".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
+
+const ZOXIDE_DB_VERSION: u32 = 3;
+
+// The database is bincode: the version, then the number of directories and each directory as
+// its path, its rank and the time of the last access
+fn parse_zoxide_db(content: &[u8]) -> Result<Vec<Entry>> {
+    fn take<'a>(content: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+        if content.len() < len {
+            bail!("unexpected end of the database");
+        }
+        let (head, rest) = content.split_at(len);
+        *content = rest;
+        Ok(head)
+    }
+    fn take_u64(content: &mut &[u8]) -> Result<u64> {
+        let bytes = take(content, 8)?;
+        Ok(u64::from_le_bytes(bytes.try_into()?))
+    }
+
+    let mut content = content;
+    take(&mut content, 4)?;
+    let count = take_u64(&mut content)?;
+    let mut entries = vec![];
+    for _ in 0..count {
+        let len = take_u64(&mut content)? as usize;
+        let path = String::from_utf8_lossy(take(&mut content, len)?).into_owned();
+        let rank = f64::from_bits(take_u64(&mut content)?);
+        // The time of the last access
+        take(&mut content, 8)?;
+        entries.extend(ranked_entry(None, &path, Some(rank)));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dests(entries: &[Entry]) -> Vec<&str> {
+        entries
+            .iter()
+            .map(|entry| entry.dest.to_str().unwrap())
+            .collect()
+    }
+
+    fn named(entries: &[Entry]) -> Vec<(Option<&str>, &str)> {
+        entries
+            .iter()
+            .map(|entry| (entry.name.as_deref(), entry.dest.to_str().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn autojump_weights_are_scores() {
+        let entries = parse_autojump("10.0\t/home/me/src\n22.5\t/tmp/with space\nbroken line\n");
+        assert_eq!(dests(&entries), ["/home/me/src", "/tmp/with space"]);
+        assert_eq!(entries[1].score, Some(22.5));
+    }
+
+    #[test]
+    fn z_paths_can_contain_bars() {
+        let entries = parse_z("/home/me/src|12|1700000000\n/tmp/a|b|3.5|1700000001\n\n");
+        assert_eq!(dests(&entries), ["/home/me/src", "/tmp/a|b"]);
+        assert_eq!(entries[0].score, Some(12.0));
+        assert_eq!(entries[1].score, Some(3.5));
+    }
+
+    #[test]
+    fn zoxide_list_has_scores_first() {
+        let entries = parse_scored_list("  100.0 /home/me/src\n   4.5 /tmp/with space\n");
+        assert_eq!(dests(&entries), ["/home/me/src", "/tmp/with space"]);
+        assert_eq!(entries[0].score, Some(100.0));
+    }
+
+    #[test]
+    fn zoxide_db_is_read() {
+        let mut db = ZOXIDE_DB_VERSION.to_le_bytes().to_vec();
+        db.extend(2u64.to_le_bytes());
+        for (path, rank) in [("/home/me/src", 8.0f64), ("/tmp", 0.5)] {
+            db.extend((path.len() as u64).to_le_bytes());
+            db.extend(path.as_bytes());
+            db.extend(rank.to_bits().to_le_bytes());
+            db.extend(1700000000u64.to_le_bytes());
+        }
+        let entries = parse_zoxide_db(&db).unwrap();
+        assert_eq!(dests(&entries), ["/home/me/src", "/tmp"]);
+        assert_eq!(entries[0].score, Some(8.0));
+
+        assert!(parse_zoxide_db(&db[..db.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn bashmarks_are_exports() {
+        let entries = parse_bashmarks(
+            "export DIR_src=\"/home/me/src\"\nexport DIR_home=\"$HOME\"\n# comment\n",
+        );
+        assert_eq!(entries[0].name.as_deref(), Some("src"));
+        assert_eq!(entries[0].dest, Path::new("/home/me/src"));
+        assert_eq!(entries[1].dest, home_dir());
+    }
+
+    #[test]
+    fn wd_and_apparix_have_names() {
+        assert_eq!(
+            named(&parse_wd("src:/home/me/src\n tmp : /tmp\n")),
+            [(Some("src"), "/home/me/src"), (Some("tmp"), "/tmp")]
+        );
+        assert_eq!(
+            named(&parse_apparix(
+                "j,src,/home/me/src\nj,csv,/tmp/a,b\ne,notes,/tmp/notes.txt\n"
+            )),
+            [(Some("src"), "/home/me/src"), (Some("csv"), "/tmp/a,b")]
+        );
+    }
+
+    #[test]
+    fn dirh_lines_or_plain_paths() {
+        let entries = parse_dirh(" 2) /home/me/src\n 1) /tmp/1) odd\n/var/log\n\n");
+        assert_eq!(dests(&entries), ["/home/me/src", "/tmp/1) odd", "/var/log"]);
+    }
+
+    #[test]
+    fn gtk_skips_remote_places() {
+        let entries =
+            parse_gtk("file:///home/me/My%20Documents Docs\nfile:///tmp\nsftp://host/dir Remote\n");
+        assert_eq!(
+            named(&entries),
+            [(Some("Docs"), "/home/me/My Documents"), (None, "/tmp")]
+        );
+    }
+
+    #[test]
+    fn xbel_skips_system_items() {
+        let entries = parse_xbel(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE xbel>
+<xbel xmlns:bookmark="http://www.freedesktop.org/standards/desktop-bookmarks">
+ <bookmark href="file:///home/me">
+  <title>Home</title>
+  <info><metadata owner="http://www.kde.org"><isSystemItem>true</isSystemItem></metadata></info>
+ </bookmark>
+ <bookmark href="file:///home/me/R%26D">
+  <title>R&amp;D &lt;team&gt;</title>
+ </bookmark>
+ <bookmark href="smb://server/share"><title>Share</title></bookmark>
+ <folder>
+  <title>Work</title>
+  <bookmark href="file:///srv/work"/>
+ </folder>
+</xbel>
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            named(&entries),
+            [(Some("R&D <team>"), "/home/me/R&D"), (None, "/srv/work")]
+        );
+
+        assert!(parse_xbel("<xbel><bookmark href=\"file:///tmp\"></xbel>").is_err());
+        assert!(parse_xbel("<xbel/>").unwrap().is_empty());
+    }
+
+    #[test]
+    fn file_uris_round_trip() {
+        assert_eq!(
+            file_uri_path("file:///tmp/a%20b").as_deref(),
+            Some("/tmp/a b")
+        );
+        assert_eq!(
+            file_uri_path("file://localhost/tmp").as_deref(),
+            Some("/tmp")
+        );
+        assert_eq!(file_uri_path("file://host/tmp"), None);
+        assert_eq!(file_uri_path("file:///tmp/%zz"), None);
+        assert_eq!(file_uri_path("https://example.com/"), None);
+
+        let path = Path::new("/tmp/R&D #1/%20 café");
+        let uri = file_uri(path);
+        assert_eq!(uri, "file:///tmp/R%26D%20%231/%2520%20caf%C3%A9");
+        assert_eq!(file_uri_path(&uri).as_deref(), path.to_str());
+    }
+
+    #[test]
+    fn top_entries_are_the_highest_ranked() {
+        let entries = parse_z("/a|1|0\n/b|30|0\n/c|20|0\n/d|5|0\n");
+        assert_eq!(dests(&top_entries(entries.clone(), None, 2)), ["/b", "/c"]);
+        assert_eq!(
+            dests(&top_entries(entries.clone(), Some(5.0), 0)),
+            ["/b", "/c", "/d"]
+        );
+        assert_eq!(top_entries(entries, None, 0).len(), 4);
+    }
+
+    #[tokio::test]
+    async fn merging_never_overwrites_bookmarks() {
+        let root = env::temp_dir().join(format!("shellmark-merge-{}", std::process::id()));
+        for dir in ["src", "other/src", "docs"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let root = root.canonicalize().unwrap();
+        let mut bookmarks = vec![Arc::new(Bookmark::new("src".into(), root.join("src")))];
+        let at = |dir: &str| root.join(dir).to_str().unwrap().to_string();
+
+        let entries = [
+            entry(None, &at("src")),
+            entry(Some("src"), &at("src")),
+            entry(None, &at("other/src")),
+            entry(Some("docs"), &at("docs")),
+            entry(Some("docs"), &at("docs")),
+            entry(None, &at("gone")),
+        ];
+        let merged = merge_entries(&mut bookmarks, entries.into_iter().flatten().collect()).await;
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            (
+                merged.imported,
+                merged.present,
+                merged.missing,
+                merged.conflicts
+            ),
+            (1, 3, 1, 1)
+        );
+        let names: Vec<_> = bookmarks.iter().map(|bm| bm.name.as_str()).collect();
+        assert_eq!(names, ["src", "docs"]);
+        assert_eq!(bookmarks[0].dest, root.join("src"));
+    }
+}
@@ -1,10 +1,22 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
 
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 pub trait Action<C> {
     fn process(&self, key: KeyEvent) -> Option<C>;
-    fn desc(&self) -> Option<(&str, &str)>;
+    fn desc(&self) -> Option<BindingDesc<'_, C>>;
+}
+
+/// Description of a key binding for the help and the status bar.
+pub struct BindingDesc<'a, C> {
+    pub keys: &'a str,
+    pub desc: &'a str,
+    // the bound command, unless it depends on the pressed key
+    pub cmd: Option<&'a C>,
 }
 
 pub struct Combo<K> {
@@ -39,17 +51,19 @@ impl Combo<()> {
 struct Binding<C, K> {
     combo: Combo<K>,
     act: Box<dyn Fn(K) -> C>,
+    cmd: Option<C>,
     desc: Option<String>,
 }
 
 impl<C, K> Binding<C, K> {
-    pub fn new<FA>(combo: Combo<K>, act: FA, desc: Option<String>) -> Binding<C, K>
+    pub fn new<FA>(combo: Combo<K>, act: FA, cmd: Option<C>, desc: Option<String>) -> Binding<C, K>
     where
         FA: Fn(K) -> C + 'static,
     {
         Binding {
             combo,
             act: Box::new(act),
+            cmd,
             desc,
         }
     }
@@ -60,25 +74,60 @@ impl<C, K> Action<C> for Binding<C, K> {
         (self.combo.check)(key).map(|k| (self.act)(k))
     }
 
-    fn desc(&self) -> Option<(&str, &str)> {
-        match (self.combo.desc.as_deref(), &self.desc.as_deref()) {
-            (Some(combo_desc), Some(action_desc)) => Some((combo_desc, action_desc)),
+    fn desc(&self) -> Option<BindingDesc<'_, C>> {
+        match (self.combo.desc.as_deref(), self.desc.as_deref()) {
+            (Some(keys), Some(desc)) => Some(BindingDesc {
+                keys,
+                desc,
+                cmd: self.cmd.as_ref(),
+            }),
             _ => None,
         }
     }
 }
 
+// A command bound to a sequence of several keys, like `g g`
+struct SequenceBinding<C> {
+    keys: Vec<KeySpec>,
+    cmd: C,
+    combo_desc: String,
+    desc: Option<String>,
+}
+
+/// Outcome of feeding a key into a [`ModeMap`] while a key sequence may be in progress.
+pub enum KeyResult<C> {
+    Command(C),
+    /// The keys so far are a prefix of a bound sequence, more keys are expected
+    Pending(Vec<KeySpec>),
+    Unbound,
+}
+
 pub struct ModeMap<S> {
     pub map: HashMap<&'static str, Vec<Box<dyn Action<S>>>>,
+    sequences: HashMap<&'static str, Vec<SequenceBinding<S>>>,
 }
 
 impl<C: Clone + 'static> ModeMap<C> {
     pub fn new() -> ModeMap<C> {
         ModeMap {
             map: HashMap::new(),
+            sequences: HashMap::new(),
         }
     }
 
+    pub fn bind_sequence<M>(&mut self, mode: M, keys: Vec<KeySpec>, cmd: C, desc: &str)
+    where
+        M: Into<&'static str>,
+    {
+        let binding = SequenceBinding {
+            combo_desc: describe_sequence(&keys),
+            keys,
+            cmd,
+            desc: Some(desc.to_string()),
+        };
+        self.sequences.entry(mode.into()).or_default().push(binding);
+    }
+
     pub fn bind_with_input<M, K, FA>(
         &mut self,
         mode: M,
@@ -90,7 +139,7 @@ impl<C: Clone + 'static> ModeMap<C> {
         FA: Fn(K) -> C + 'static,
         K: 'static,
     {
-        let binding = Binding::new(combo, act, desc);
+        let binding = Binding::new(combo, act, None, desc);
         self.map
             .entry(mode.into())
             .or_default()
@@ -101,8 +150,9 @@ impl<C: Clone + 'static> ModeMap<C> {
     where
         M: Into<&'static str>,
     {
+        let bound_cmd = cmd.clone();
         let act = move |_| cmd.clone();
-        let binding = Binding::new(combo, Box::new(act), desc);
+        let binding = Binding::new(combo, Box::new(act), Some(bound_cmd), desc);
 
         self.map
             .entry(mode.into())
@@ -129,12 +179,56 @@ impl<C: Clone + 'static> ModeMap<C> {
         None
     }
 
-    pub fn descriptions<M: Into<&'static str>>(&self, mode: M) -> Vec<(&str, &str)> {
+    /// Process a key that follows the `pending` keys of a sequence typed so far.
+    ///
+    /// Sequences take precedence over single keys. A key that doesn't continue the pending
+    /// sequence cancels it, and is then processed on its own.
+    pub fn process_sequence<M: Into<&'static str>>(
+        &self,
+        mode: M,
+        pending: &[KeySpec],
+        key: KeyEvent,
+    ) -> KeyResult<C> {
+        let mode = mode.into();
+        let mut keys = pending.to_vec();
+        keys.push(KeySpec::new(key.code, key.modifiers));
+
+        if let Some(sequences) = self.sequences.get(mode) {
+            if let Some(binding) = sequences.iter().find(|b| b.keys == keys) {
+                return KeyResult::Command(binding.cmd.clone());
+            }
+            if sequences.iter().any(|b| b.keys.starts_with(&keys)) {
+                return KeyResult::Pending(keys);
+            }
+        }
+
+        if !pending.is_empty() {
+            return self.process_sequence(mode, &[], key);
+        }
+        match self.process(mode, key) {
+            Some(cmd) => KeyResult::Command(cmd),
+            None => KeyResult::Unbound,
+        }
+    }
+
+    pub fn descriptions<M: Into<&'static str>>(&self, mode: M) -> Vec<BindingDesc<'_, C>> {
+        let mode = mode.into();
         let mut descs = vec![];
-        if let Some(mapping) = self.map.get(mode.into()) {
+        if let Some(mapping) = self.map.get(mode) {
             for act in mapping {
-                if let Some((combo_desc, action_desc)) = act.desc() {
-                    descs.push((combo_desc, action_desc));
+                if let Some(desc) = act.desc() {
+                    descs.push(desc);
+                }
+            }
+        }
+        if let Some(sequences) = self.sequences.get(mode) {
+            for binding in sequences {
+                if let Some(desc) = binding.desc.as_deref() {
+                    descs.push(BindingDesc {
+                        keys: binding.combo_desc.as_str(),
+                        desc,
+                        cmd: Some(&binding.cmd),
+                    });
                 }
             }
         }
@@ -143,260 +237,259 @@ impl<C: Clone + 'static> ModeMap<C> {
     }
 }
 
-// Common keybindings
-// TODO: maybe simplify this with macros
-
-pub fn any_char() -> Combo<char> {
-    Combo::with_input(
-        |key| match key {
-            KeyEvent {
-                code: KeyCode::Char(c),
-                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
-                ..
-            } => Some(c),
-            _ => None,
-        },
-        None,
-    )
+/// A key together with its modifiers, parsed from specs like `ctrl-n`, `alt-shift-k` or `f5`.
+///
+/// Character keys carry their case in the character itself, so `shift-k` is the same key as `K`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeySpec {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
 }
 
-pub fn char(ch: char) -> Combo<()> {
-    Combo::with_match(
-        move |key| {
-            matches!(
-                key,
-                KeyEvent {
-                    code: KeyCode::Char(c),
-                    modifiers: KeyModifiers::NONE,
-                    ..
-                } if c == ch
-            )
-        },
-        Some(format!("{}", ch)),
-    )
-}
+impl KeySpec {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => KeySpec {
+                code: KeyCode::Char(c.to_ascii_uppercase()),
+                modifiers: modifiers - KeyModifiers::SHIFT,
+            },
+            _ => KeySpec { code, modifiers },
+        }
+    }
 
-pub fn ctrl_c() -> Combo<()> {
-    Combo::with_match(
-        |key: KeyEvent| {
-            matches!(
-                key,
-                KeyEvent {
-                    code: KeyCode::Char('c'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                }
-            )
-        },
-        Some("C-c".to_string()),
-    )
+    pub fn matches(&self, key: KeyEvent) -> bool {
+        *self == KeySpec::new(key.code, key.modifiers)
+    }
 }
 
-pub fn ctrl_n() -> Combo<()> {
-    Combo::with_match(
-        |key: KeyEvent| {
-            matches!(
-                key,
-                KeyEvent {
-                    code: KeyCode::Char('n'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                }
-            )
-        },
-        Some("C-n".to_string()),
-    )
-}
+const KEY_NAMES: [(&str, KeyCode); 18] = [
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("escape", KeyCode::Esc),
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("space", KeyCode::Char(' ')),
+    ("minus", KeyCode::Char('-')),
+];
+
+impl FromStr for KeySpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        loop {
+            let (prefix, modifier) = match rest.split_once('-') {
+                Some((prefix, tail)) if !tail.is_empty() => (prefix, tail),
+                _ => break,
+            };
+            modifiers |= match prefix.to_lowercase().as_str() {
+                "ctrl" | "c" => KeyModifiers::CONTROL,
+                "alt" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier `{prefix}` in key `{s}`")),
+            };
+            rest = modifier;
+        }
 
-pub fn ctrl_p() -> Combo<()> {
-    Combo::with_match(
-        |key: KeyEvent| {
-            matches!(
-                key,
-                KeyEvent {
-                    code: KeyCode::Char('p'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => {
+                let name = rest.to_lowercase();
+                let named = KEY_NAMES.iter().find(|(key_name, _)| *key_name == name);
+                match (named, name.strip_prefix('f').map(str::parse::<u8>)) {
+                    (Some((_, code)), _) => *code,
+                    (None, Some(Ok(n @ 1..=12))) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key `{rest}` in key `{s}`")),
                 }
-            )
-        },
-        Some("C-p".to_string()),
-    )
-}
+            }
+        };
 
-pub fn ctrl_o() -> Combo<()> {
-    Combo::with_match(
-        |key: KeyEvent| {
-            matches!(
-                key,
-                KeyEvent {
-                    code: KeyCode::Char('o'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                }
-            )
-        },
-        Some("C-o".to_string()),
-    )
+        Ok(KeySpec::new(code, modifiers))
+    }
 }
 
-pub fn ctrl_j() -> Combo<()> {
-    Combo::with_match(
-        |key: KeyEvent| {
-            matches!(
-                key,
-                KeyEvent {
-                    code: KeyCode::Char('j'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                }
-            )
-        },
-        Some("C-j".to_string()),
-    )
+impl Display for KeySpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "C-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "M-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "S-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            KeyCode::BackTab => write!(f, "BackTab"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            code => write!(f, "{code:?}"),
+        }
+    }
 }
 
-pub fn ctrl_k() -> Combo<()> {
-    Combo::with_match(
-        |key: KeyEvent| {
-            matches!(
-                key,
-                KeyEvent {
-                    code: KeyCode::Char('k'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                }
-            )
-        },
-        Some("C-k".to_string()),
-    )
+/// Parse a whitespace-separated sequence of key specs, like `ctrl-x ctrl-d` or `g g`.
+pub fn parse_sequence(s: &str) -> Result<Vec<KeySpec>, String> {
+    let keys = s
+        .split_whitespace()
+        .map(KeySpec::from_str)
+        .collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err("empty key sequence".to_string());
+    }
+    Ok(keys)
 }
 
-#[allow(non_snake_case)]
-pub fn ctrl_K() -> Combo<()> {
-    Combo::with_match(
-        |key: KeyEvent| {
-            matches!(
-                key,
-                KeyEvent {
-                    code: KeyCode::Char('K'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                }
-            )
-        },
-        Some("C-K".to_string()),
-    )
+pub fn describe_sequence(keys: &[KeySpec]) -> String {
+    keys.iter()
+        .map(KeySpec::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
-pub fn arrow_down() -> Combo<()> {
-    Combo::with_match(
-        |key: KeyEvent| {
-            matches!(
-                key,
-                KeyEvent {
-                    code: KeyCode::Down,
-                    modifiers: KeyModifiers::NONE,
-                    ..
-                }
-            )
-        },
-        Some("Down".to_string()),
-    )
+impl Combo<()> {
+    pub fn key(spec: KeySpec) -> Self {
+        Combo::with_match(move |key| spec.matches(key), Some(spec.to_string()))
+    }
 }
 
-pub fn arrow_up() -> Combo<()> {
-    Combo::with_match(
-        |key: KeyEvent| {
-            matches!(
-                key,
-                KeyEvent {
-                    code: KeyCode::Up,
-                    modifiers: KeyModifiers::NONE,
-                    ..
-                }
-            )
+pub fn any_char() -> Combo<char> {
+    Combo::with_input(
+        |key| match key {
+            KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                ..
+            } => Some(c),
+            _ => None,
         },
-        Some("Up".to_string()),
+        Some("<char>".to_string()),
     )
 }
 
-pub fn enter() -> Combo<()> {
-    Combo::with_match(
-        |key: KeyEvent| {
-            matches!(
-                key,
-                KeyEvent {
-                    code: KeyCode::Enter,
-                    modifiers: KeyModifiers::NONE,
-                    ..
-                }
-            )
-        },
-        Some("Enter".to_string()),
-    )
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-pub fn backspace() -> Combo<()> {
-    Combo::with_match(
-        |key: KeyEvent| {
-            matches!(
-                key,
-                KeyEvent {
-                    code: KeyCode::Backspace,
-                    modifiers: KeyModifiers::NONE,
-                    ..
-                }
-            )
-        },
-        Some("Backspace".to_string()),
-    )
-}
+    fn spec(code: KeyCode, modifiers: KeyModifiers) -> KeySpec {
+        KeySpec { code, modifiers }
+    }
 
-pub fn ctrl_backspace() -> Combo<()> {
-    Combo::with_match(
-        |key: KeyEvent| {
-            matches!(
-                key,
-                KeyEvent {
-                    code: KeyCode::Backspace,
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                }
-            )
-        },
-        Some("C-Backspace".to_string()),
-    )
-}
+    #[test]
+    fn parses_modifiers_and_named_keys() {
+        assert_eq!(
+            "ctrl-n".parse(),
+            Ok(spec(KeyCode::Char('n'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            "C-M-x".parse(),
+            Ok(spec(
+                KeyCode::Char('x'),
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            ))
+        );
+        assert_eq!("f5".parse(), Ok(spec(KeyCode::F(5), KeyModifiers::NONE)));
+        assert_eq!(
+            "PageDown".parse(),
+            Ok(spec(KeyCode::PageDown, KeyModifiers::NONE))
+        );
+        assert_eq!(
+            "alt-minus".parse(),
+            Ok(spec(KeyCode::Char('-'), KeyModifiers::ALT))
+        );
+        // A trailing `-` is the key itself
+        assert_eq!(
+            "ctrl--".parse(),
+            Ok(spec(KeyCode::Char('-'), KeyModifiers::CONTROL))
+        );
+    }
 
-pub fn f1() -> Combo<()> {
-    Combo::with_match(
-        |key: KeyEvent| {
-            matches!(
-                key,
-                KeyEvent {
-                    code: KeyCode::F(1),
-                    modifiers: KeyModifiers::NONE,
-                    ..
-                }
-            )
-        },
-        Some("F1".to_string()),
-    )
-}
+    #[test]
+    fn shift_is_part_of_the_character() {
+        assert_eq!(
+            "alt-shift-k".parse(),
+            Ok(spec(KeyCode::Char('K'), KeyModifiers::ALT))
+        );
+        assert_eq!("alt-shift-k".parse::<KeySpec>(), "alt-K".parse());
+        assert_eq!(
+            "shift-tab".parse(),
+            Ok(spec(KeyCode::Tab, KeyModifiers::SHIFT))
+        );
+    }
 
-pub fn esc() -> Combo<()> {
-    Combo::with_match(
-        |key: KeyEvent| {
-            matches!(
-                key,
-                KeyEvent {
-                    code: KeyCode::Esc,
-                    modifiers: KeyModifiers::NONE,
-                    ..
-                }
-            )
-        },
-        Some("Esc".to_string()),
-    )
+    #[test]
+    fn rejects_unknown_keys_and_modifiers() {
+        assert!("hyper-k".parse::<KeySpec>().is_err());
+        assert!("f13".parse::<KeySpec>().is_err());
+        assert!("ctrl-nope".parse::<KeySpec>().is_err());
+        assert!(parse_sequence("  ").is_err());
+    }
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    fn vi_map() -> ModeMap<&'static str> {
+        let mut map = ModeMap::new();
+        map.bind_sequence("vi", parse_sequence("d d").unwrap(), "delete", "Delete");
+        map.bind_sequence("vi", parse_sequence("g g").unwrap(), "first", "First");
+        map.bind("vi", Combo::key("x".parse().unwrap()), "cut", "Cut");
+        map
+    }
+
+    #[test]
+    fn sequences_wait_for_their_next_key() {
+        let map = vi_map();
+        let pending = match map.process_sequence("vi", &[], key('d')) {
+            KeyResult::Pending(keys) => keys,
+            _ => panic!("`d` should start a sequence"),
+        };
+        assert!(matches!(
+            map.process_sequence("vi", &pending, key('d')),
+            KeyResult::Command("delete")
+        ));
+    }
+
+    #[test]
+    fn a_key_breaking_a_sequence_is_not_lost() {
+        let map = vi_map();
+        let pending = parse_sequence("d").unwrap();
+        assert!(matches!(
+            map.process_sequence("vi", &pending, key('x')),
+            KeyResult::Command("cut")
+        ));
+        // It can start another sequence too
+        assert!(matches!(
+            map.process_sequence("vi", &pending, key('g')),
+            KeyResult::Pending(keys) if keys == parse_sequence("g").unwrap()
+        ));
+        assert!(matches!(
+            map.process_sequence("vi", &pending, key('z')),
+            KeyResult::Unbound
+        ));
+    }
+
+    #[test]
+    fn sequences_round_trip_through_their_description() {
+        let keys = parse_sequence("ctrl-x ctrl-d").unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(describe_sequence(&keys), "C-x C-d");
+        assert_eq!(parse_sequence(&describe_sequence(&keys)), Ok(keys));
+    }
 }
@@ -2,12 +2,21 @@ mod add;
 mod bookmarks;
 mod browse;
 mod cli;
+mod completions;
+mod config;
+mod copy;
 mod diag;
+mod export;
+mod history;
+mod import;
 mod keys;
 mod plug;
 mod search;
 mod shell;
 mod storage;
+mod suggest;
+mod sync;
+mod visits;
 
 use anyhow::Result;
 use clap::Parser;
@@ -23,7 +32,15 @@ use tracing_subscriber::EnvFilter;
 use crate::add::add_cmd;
 use crate::browse::browse_cmd;
 use crate::cli::Command;
+use crate::completions::completions_cmd;
+use crate::config::config_cmd;
+use crate::copy::copy_cmd;
 use crate::diag::diag_cmd;
+use crate::export::export_cmd;
+use crate::import::import_cmd;
+use crate::suggest::suggest_cmd;
+use crate::sync::sync_cmd;
+use crate::visits::visit_cmd;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -33,15 +50,49 @@ async fn main() -> Result<(), Box<dyn Error>> {
         .with_writer(std::io::stderr)
         .init();
     let opts = cli::Opts::parse();
+    config::load().await?;
 
     let output = match opts.command {
         Some(cli::Command::Add(add_cmd_opts)) => {
             add_cmd(add_cmd_opts).await?.to_output(opts.out_type)
         }
-        Some(cli::Command::Browse(_)) => browse_cmd().await?.to_output(opts.out_type),
-        Some(cli::Command::Plug(plug_cmd_opts)) => plug_cmd(plug_cmd_opts).to_output(opts.out_type),
-        None => browse_cmd().await?.to_output(opts.out_type),
+        Some(cli::Command::Browse(browse_cmd_opts)) => {
+            browse_cmd(browse_cmd_opts).await?.to_output(opts.out_type)
+        }
+        Some(cli::Command::Copy(copy_cmd_opts)) => {
+            copy_cmd(copy_cmd_opts).await?.to_output(opts.out_type)
+        }
+        Some(cli::Command::Plug(plug_cmd_opts)) => {
+            plug_cmd(plug_cmd_opts).await?.to_output(opts.out_type)
+        }
+        None => browse_cmd(cli::BrowseCmd {
+            query: opts.query,
+            ..Default::default()
+        })
+        .await?
+        .to_output(opts.out_type),
         Some(Command::Diag(_)) => diag_cmd().await?.to_output(opts.out_type),
+        Some(Command::Config(config_cmd_opts)) => {
+            config_cmd(config_cmd_opts)?.to_output(opts.out_type)
+        }
+        Some(Command::Completions(completions_cmd_opts)) => completions_cmd(completions_cmd_opts)
+            .await?
+            .to_output(opts.out_type),
+        Some(Command::Visit(visit_cmd_opts)) => {
+            visit_cmd(visit_cmd_opts).await?.to_output(opts.out_type)
+        }
+        Some(Command::Suggest(suggest_cmd_opts)) => suggest_cmd(suggest_cmd_opts)
+            .await?
+            .to_output(opts.out_type),
+        Some(Command::Import(import_cmd_opts)) => {
+            import_cmd(import_cmd_opts).await?.to_output(opts.out_type)
+        }
+        Some(Command::Sync(sync_cmd_opts)) => {
+            sync_cmd(sync_cmd_opts).await?.to_output(opts.out_type)
+        }
+        Some(Command::Export(export_cmd_opts)) => export_cmd(export_cmd_opts, opts.out_type)
+            .await?
+            .to_output(opts.out_type),
     };
 
     if let Some(output) = output {
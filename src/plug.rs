@@ -1,20 +1,76 @@
+use std::{ffi::OsString, sync::Arc};
+
+use anyhow::Result;
+
 use crate::{
+    bookmarks::{read_bookmarks, Bookmark},
     cli,
+    completions::subcommand_names,
     shell::{self, OutputType},
 };
-pub struct PlugCommand {
-    name: String,
+
+pub enum PlugCommand {
+    Integration { name: String, track: bool },
+    // Bookmarks to register as zsh named directories
+    NamedDirs(Vec<Arc<Bookmark>>),
 }
 
 impl shell::Output for PlugCommand {
     fn to_output(&self, out_type: OutputType) -> Option<String> {
-        let content = inner_content(out_type);
-        content.map(|x| x.replace("{name}", &self.name))
+        match self {
+            PlugCommand::Integration { name, track } => {
+                let mut content = inner_content(out_type)?.to_string();
+                if *track {
+                    content.push_str(track_content(out_type)?);
+                }
+                Some(
+                    content
+                        .replace("{name}", name)
+                        .replace("{subcommands}", &subcommand_names()),
+                )
+            }
+            PlugCommand::NamedDirs(bookmarks) => match out_type {
+                OutputType::Zsh => Some(named_dirs(bookmarks)),
+                _ => None,
+            },
+        }
+    }
+}
+
+pub async fn plug_cmd(opts: cli::PlugCmd) -> Result<PlugCommand> {
+    if opts.named_dirs {
+        Ok(PlugCommand::NamedDirs(read_bookmarks().await?))
+    } else {
+        Ok(PlugCommand::Integration {
+            name: opts.name,
+            track: opts.track,
+        })
     }
 }
 
-pub fn plug_cmd(opts: cli::PlugCmd) -> PlugCommand {
-    PlugCommand { name: opts.name }
+// Replace the named directories registered before, so that deleted bookmarks go away too
+fn named_dirs(bookmarks: &[Arc<Bookmark>]) -> String {
+    let out_type = OutputType::Zsh;
+    // `=` separates the name from the directory and `/` ends the name in `~name/...`
+    let bookmarks: Vec<_> = bookmarks
+        .iter()
+        .filter(|bm| !bm.name.is_empty() && !bm.name.contains(['=', '/']))
+        .collect();
+
+    let mut out = String::new();
+    out.push_str("typeset -ga __shellmark_named_dirs\n");
+    out.push_str("(( ${#__shellmark_named_dirs} )) && unhash -d -- \"${__shellmark_named_dirs[@]}\" 2>/dev/null\n");
+    let names: Vec<_> = bookmarks
+        .iter()
+        .map(|bm| shell::quote(&bm.name, out_type))
+        .collect();
+    out.push_str(&format!("__shellmark_named_dirs=({})\n", names.join(" ")));
+    for bm in bookmarks {
+        let mut entry = OsString::from(format!("{}=", bm.name));
+        entry.push(&bm.dest);
+        out.push_str(&format!("hash -d -- {}\n", shell::quote(entry, out_type)));
+    }
+    out
 }
 
 #[cfg(not(target_os = "windows"))]
@@ -24,6 +80,10 @@ fn inner_content(out_type: OutputType) -> Option<&'static str> {
         OutputType::Fish => Some(include_str!("../integration/s.fish")),
         OutputType::PowerShell => Some(include_str!("../integration/s.ps1")),
         OutputType::Posix => Some(include_str!("../integration/s.sh")),
+        OutputType::Zsh => Some(include_str!("../integration/s.zsh")),
+        OutputType::Nushell => Some(include_str!("../integration/s.nu")),
+        OutputType::Elvish => Some(include_str!("../integration/s.elv")),
+        OutputType::Xonsh => Some(include_str!("../integration/s.xsh")),
     }
 }
 
@@ -34,5 +94,38 @@ fn inner_content(out_type: OutputType) -> Option<&'static str> {
         OutputType::Fish => Some(include_str!("..\\integration\\s.fish")),
         OutputType::PowerShell => Some(include_str!("..\\integration\\s.ps1")),
         OutputType::Posix => Some(include_str!("..\\integration\\s.sh")),
+        OutputType::Zsh => Some(include_str!("..\\integration\\s.zsh")),
+        OutputType::Nushell => Some(include_str!("..\\integration\\s.nu")),
+        OutputType::Elvish => Some(include_str!("..\\integration\\s.elv")),
+        OutputType::Xonsh => Some(include_str!("..\\integration\\s.xsh")),
+    }
+}
+
+// Hooks that count the directories the shell changes to as visits
+#[cfg(not(target_os = "windows"))]
+fn track_content(out_type: OutputType) -> Option<&'static str> {
+    match out_type {
+        OutputType::Plain => None,
+        OutputType::Fish => Some(include_str!("../integration/track/track.fish")),
+        OutputType::PowerShell => Some(include_str!("../integration/track/track.ps1")),
+        OutputType::Posix => Some(include_str!("../integration/track/track.sh")),
+        OutputType::Zsh => Some(include_str!("../integration/track/track.zsh")),
+        OutputType::Nushell => Some(include_str!("../integration/track/track.nu")),
+        OutputType::Elvish => Some(include_str!("../integration/track/track.elv")),
+        OutputType::Xonsh => Some(include_str!("../integration/track/track.xsh")),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn track_content(out_type: OutputType) -> Option<&'static str> {
+    match out_type {
+        OutputType::Plain => None,
+        OutputType::Fish => Some(include_str!("..\\integration\\track\\track.fish")),
+        OutputType::PowerShell => Some(include_str!("..\\integration\\track\\track.ps1")),
+        OutputType::Posix => Some(include_str!("..\\integration\\track\\track.sh")),
+        OutputType::Zsh => Some(include_str!("..\\integration\\track\\track.zsh")),
+        OutputType::Nushell => Some(include_str!("..\\integration\\track\\track.nu")),
+        OutputType::Elvish => Some(include_str!("..\\integration\\track\\track.elv")),
+        OutputType::Xonsh => Some(include_str!("..\\integration\\track\\track.xsh")),
     }
 }
@@ -1,26 +1,104 @@
-use std::sync::Arc;
+use std::{borrow::Cow, sync::Arc};
 
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
 
-use crate::{bookmarks::Bookmark, storage::friendly_path};
+use crate::{
+    bookmarks::Bookmark,
+    config::{CaseMatching, MatcherConfig, MatcherKind, SortOrder},
+    storage::friendly_path,
+    visits::Visits,
+};
+
+pub enum Matcher {
+    Fuzzy(Box<SkimMatcherV2>),
+    Substring(CaseMatching),
+}
+
+impl Matcher {
+    pub fn from_config(config: &MatcherConfig) -> Self {
+        match config.kind {
+            MatcherKind::Fuzzy => {
+                let matcher = SkimMatcherV2::default();
+                let matcher = match config.case {
+                    CaseMatching::Smart => matcher.smart_case(),
+                    CaseMatching::Ignore => matcher.ignore_case(),
+                    CaseMatching::Respect => matcher.respect_case(),
+                };
+                Matcher::Fuzzy(Box::new(matcher))
+            }
+            MatcherKind::Substring => Matcher::Substring(config.case),
+        }
+    }
+
+    /// Score how well the text matches the pattern, higher is better.
+    pub fn score(&self, text: &str, pattern: &str) -> Option<i64> {
+        match self {
+            Matcher::Fuzzy(matcher) => matcher.fuzzy_match(text, pattern),
+            Matcher::Substring(case) => {
+                let ignore_case = match case {
+                    CaseMatching::Smart => !pattern.chars().any(char::is_uppercase),
+                    CaseMatching::Ignore => true,
+                    CaseMatching::Respect => false,
+                };
+                // Lowercasing can change the length, so the position is taken from the same text
+                let (text, pattern) = if ignore_case {
+                    (
+                        Cow::Owned(text.to_lowercase()),
+                        Cow::Owned(pattern.to_lowercase()),
+                    )
+                } else {
+                    (Cow::Borrowed(text), Cow::Borrowed(pattern))
+                };
+                // Prefer matches closer to the start of the text
+                text.find(pattern.as_ref())
+                    .map(|pos| (text.len() - pos) as i64 + 1)
+            }
+        }
+    }
+}
+
+/// Indices of all bookmarks in the given sort order.
+pub fn sort_bookmarks(
+    bookmarks: &[Arc<Bookmark>],
+    order: SortOrder,
+    visits: &Visits,
+) -> Vec<usize> {
+    let mut indices: Vec<_> = (0..bookmarks.len()).collect();
+    match order {
+        SortOrder::Added => {}
+        SortOrder::Name => indices.sort_by(|&a, &b| bookmarks[a].name.cmp(&bookmarks[b].name)),
+        SortOrder::Path => indices.sort_by(|&a, &b| bookmarks[a].dest.cmp(&bookmarks[b].dest)),
+        SortOrder::Frecent => {
+            let frecency: Vec<_> = bookmarks
+                .iter()
+                .map(|bm| visits.frecency(&bm.dest))
+                .collect();
+            indices.sort_by(|&a, &b| frecency[b].total_cmp(&frecency[a]));
+        }
+    }
+    indices
+}
 
 pub fn find_matches(
-    matcher: &SkimMatcherV2,
+    matcher: &Matcher,
     bookmarks: &[Arc<Bookmark>],
+    order: SortOrder,
+    visits: &Visits,
     pattern: String,
 ) -> Vec<usize> {
     // Rank all bookmarks using fuzzy matcher
-    let mut scores: Vec<_> = bookmarks
-        .iter()
-        .map(|bm| {
-            matcher.fuzzy_match(
+    let mut scores: Vec<_> = sort_bookmarks(bookmarks, order, visits)
+        .into_iter()
+        .map(|idx| {
+            let bm = &bookmarks[idx];
+            let score = matcher.score(
                 &format!("{} {}", bm.name, friendly_path(&bm.dest)),
                 &pattern,
-            )
+            );
+            (idx, score)
         })
-        .enumerate()
         .collect();
-    // Reverse sort the scores
+    // Reverse sort the scores, the sort is stable so equal scores stay in the sort order
     scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
 
     // Pick the matches starting from the "best" one
@@ -35,3 +113,47 @@ pub fn find_matches(
 
     matches
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn substring(case: CaseMatching) -> Matcher {
+        Matcher::Substring(case)
+    }
+
+    #[test]
+    fn substring_prefers_matches_near_the_start() {
+        let matcher = substring(CaseMatching::Smart);
+        let early = matcher.score("projects/shellmark", "proj").unwrap();
+        let late = matcher.score("my/projects", "proj").unwrap();
+        assert!(early > late);
+        assert_eq!(matcher.score("projects", "xyz"), None);
+    }
+
+    #[test]
+    fn substring_case_matching() {
+        assert!(substring(CaseMatching::Smart)
+            .score("Projects", "proj")
+            .is_some());
+        assert_eq!(
+            substring(CaseMatching::Smart).score("projects", "Proj"),
+            None
+        );
+        assert!(substring(CaseMatching::Ignore)
+            .score("projects", "PROJ")
+            .is_some());
+        assert_eq!(
+            substring(CaseMatching::Respect).score("Projects", "proj"),
+            None
+        );
+    }
+
+    #[test]
+    fn substring_survives_lowercase_growing_the_text() {
+        // `İ` takes 2 bytes, but 3 when lowercased
+        let matcher = substring(CaseMatching::Ignore);
+        assert!(matcher.score("İİİİx", "x").is_some());
+        assert!(matcher.score("İstanbul", "i̇stanbul").is_some());
+    }
+}
@@ -1,6 +1,16 @@
-use std::env;
+use std::{
+    borrow::Cow,
+    env,
+    ffi::OsStr,
+    path::Path,
+    process::{Command, Stdio},
+};
 
+use anyhow::{anyhow, Context, Result};
 use clap::{Parser, ValueEnum};
+use tracing::warn;
+
+use crate::config;
 
 pub trait Output {
     fn to_output(&self, out_type: OutputType) -> Option<String>;
@@ -21,19 +31,781 @@ impl<A: Output> Output for Option<A> {
     }
 }
 
-#[derive(Parser, Default, Copy, Clone, ValueEnum)]
+#[derive(Parser, Debug, Default, Copy, Clone, ValueEnum)]
 pub enum OutputType {
     #[default]
     Plain,
     Posix,
+    Zsh,
     Fish,
     #[clap(name = "powershell")]
     PowerShell,
+    #[clap(name = "nu")]
+    Nushell,
+    Elvish,
+    Xonsh,
+}
+
+/// Quote a string or a path so that the shell of the given type reads it back as exactly the
+/// same word, whatever characters it contains.
+///
+/// Plain output is quoted for POSIX shells. Bytes that aren't valid UTF-8 are written as escapes
+/// for POSIX shells, zsh and fish, and replaced with a warning for the other shells, which can't
+/// represent them.
+pub(crate) fn quote(s: impl AsRef<OsStr>, out_type: OutputType) -> String {
+    let s = s.as_ref();
+    let byte_escape = match out_type {
+        // A command substitution only strips newlines, which these bytes never are
+        OutputType::Plain | OutputType::Posix => |byte| format!("\"$(printf '\\{byte:03o}')\""),
+        OutputType::Zsh => |byte| format!("$'\\x{byte:02x}'"),
+        OutputType::Fish => |byte| format!("\\X{byte:02x}"),
+        // Adjacent strings aren't concatenated in these shells, so quote everything at once
+        OutputType::PowerShell | OutputType::Nushell | OutputType::Elvish | OutputType::Xonsh => {
+            let text = s.to_string_lossy();
+            if let Cow::Owned(_) = text {
+                warn!(
+                    "{:?} isn't valid Unicode, the shell gets {} instead",
+                    s, text
+                );
+            }
+            return quote_str(&text, out_type);
+        }
+    };
+
+    let mut out = String::new();
+    for chunk in os_str_chunks(s) {
+        match chunk {
+            Ok(text) => out.push_str(&quote_str(&text, out_type)),
+            Err(byte) => out.push_str(&byte_escape(byte)),
+        }
+    }
+    if out.is_empty() {
+        out = quote_str("", out_type);
+    }
+    out
+}
+
+// Quote valid text as a single-quoted string, where nothing but the quotes is special, or as a
+// string with escapes for Nushell and Xonsh, whose single-quoted strings work differently
+fn quote_str(s: &str, out_type: OutputType) -> String {
+    match out_type {
+        OutputType::Plain | OutputType::Posix | OutputType::Zsh => {
+            format!("'{}'", s.replace('\'', r"'\''"))
+        }
+        OutputType::Fish => format!("'{}'", s.replace('\\', r"\\").replace('\'', r"\'")),
+        OutputType::PowerShell => {
+            let mut out = String::with_capacity(s.len() + 2);
+            out.push('\'');
+            for c in s.chars() {
+                // PowerShell treats typographic single quotes as quotes too
+                if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}') {
+                    out.push(c);
+                }
+                out.push(c);
+            }
+            out.push('\'');
+            out
+        }
+        OutputType::Nushell => {
+            let mut out = String::with_capacity(s.len() + 2);
+            out.push('"');
+            for c in s.chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\t' => out.push_str("\\t"),
+                    c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+            out
+        }
+        OutputType::Elvish => format!("'{}'", s.replace('\'', "''")),
+        // A Python string literal
+        OutputType::Xonsh => {
+            let mut out = String::with_capacity(s.len() + 2);
+            out.push('\'');
+            for c in s.chars() {
+                match c {
+                    '\'' => out.push_str("\\'"),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\t' => out.push_str("\\t"),
+                    c if c.is_control() => out.push_str(&format!("\\x{:02x}", c as u32)),
+                    c => out.push(c),
+                }
+            }
+            out.push('\'');
+            out
+        }
+    }
+}
+
+// Split a string into runs of valid UTF-8 and single bytes that aren't
+#[cfg(unix)]
+fn os_str_chunks(s: &OsStr) -> Vec<Result<Cow<'_, str>, u8>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut chunks = vec![];
+    for chunk in s.as_bytes().utf8_chunks() {
+        if !chunk.valid().is_empty() {
+            chunks.push(Ok(Cow::Borrowed(chunk.valid())));
+        }
+        chunks.extend(chunk.invalid().iter().map(|byte| Err(*byte)));
+    }
+    chunks
+}
+
+// Paths that aren't valid Unicode can't be passed to the shell as text, so replace the rest
+#[cfg(not(unix))]
+fn os_str_chunks(s: &OsStr) -> Vec<Result<Cow<'_, str>, u8>> {
+    vec![Ok(s.to_string_lossy())]
+}
+
+/// Replace `{var}` placeholders in a command template with quoted values of the variables.
+///
+/// `{{` and `}}` stand for literal braces.
+pub(crate) fn expand_template(
+    template: &str,
+    vars: &[(&str, &OsStr)],
+    out_type: OutputType,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("unclosed placeholder `{{{name}`")),
+                    }
+                }
+                match vars.iter().find(|(var, _)| *var == name) {
+                    Some((_, value)) => out.push_str(&quote(value, out_type)),
+                    None => {
+                        let names: Vec<_> =
+                            vars.iter().map(|(var, _)| format!("{{{var}}}")).collect();
+                        return Err(format!(
+                            "unknown placeholder `{{{}}}`, expected one of {}",
+                            name,
+                            names.join(", ")
+                        ));
+                    }
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+/// Open a file or a directory with the given opener or the system's default application.
+///
+/// The opener is split into the program and its arguments like a shell would, see
+/// [`split_command`]. It's started detached from the terminal and isn't waited for.
+pub(crate) fn open_with_system(path: &Path, opener: Option<&str>) -> Result<()> {
+    // Not `start`, it's run by cmd.exe, which would take a `&` in the path for another command
+    let default_opener = if cfg!(windows) {
+        "explorer.exe"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    let opener = opener.unwrap_or(default_opener);
+    let words = split_command(opener)
+        .map_err(|err| anyhow!("Couldn't parse the opener `{opener}`: {err}"))?;
+    let (program, args) = words.split_first().context("The opener command is empty")?;
+
+    let mut cmd = Command::new(program);
+    cmd.args(args)
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // Keep the opener running when the terminal goes away
+        cmd.process_group(0);
+    }
+    cmd.spawn()
+        .with_context(|| format!("Couldn't run the opener `{opener}`"))?;
+
+    Ok(())
+}
+
+/// Split a command line into words with the quoting rules of POSIX shells: single quotes keep
+/// everything, double quotes and backslashes escape.
+///
+/// On Windows, where backslashes separate paths, they only escape `"` in double quotes.
+pub(crate) fn split_command(command: &str) -> Result<Vec<String>, String> {
+    let escapes = !cfg!(windows);
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unclosed `'`".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\')
+                            if chars.peek() == Some(&'"')
+                                || escapes && matches!(chars.peek(), Some('\\' | '$' | '`')) =>
+                        {
+                            word.extend(chars.next());
+                        }
+                        Some(c) => word.push(c),
+                        None => return Err("unclosed `\"`".to_string()),
+                    }
+                }
+            }
+            '\\' if escapes => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err("trailing `\\`".to_string()),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
 }
 
 pub(crate) fn is_editor_set() -> bool {
+    editor_command().is_some()
+}
+
+/// Command to open a file in the editor: the one from config, or `$EDITOR` when it's set.
+pub(crate) fn editor_command() -> Option<String> {
+    if let Some(editor) = &config::get().editor {
+        return Some(editor.clone());
+    }
     match env::var("EDITOR") {
-        Ok(editor) => !editor.is_empty(),
-        _ => false,
+        Ok(editor) if !editor.is_empty() => Some("$EDITOR".to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn expand(template: &str) -> Result<String, String> {
+        let vars = [
+            ("dest", OsStr::new("/tmp/it's here")),
+            ("dir", OsStr::new("/tmp")),
+        ];
+        expand_template(template, &vars, OutputType::Posix)
+    }
+
+    #[test]
+    fn template_placeholders_are_quoted() {
+        assert_eq!(
+            expand("code {dest}"),
+            Ok(r"code '/tmp/it'\''s here'".to_string())
+        );
+        assert_eq!(
+            expand("tmux new-window -c {dir}"),
+            Ok("tmux new-window -c '/tmp'".to_string())
+        );
+    }
+
+    #[test]
+    fn template_braces_can_be_escaped() {
+        assert_eq!(
+            expand("awk '{{print}}' {dir}"),
+            Ok("awk '{print}' '/tmp'".to_string())
+        );
+    }
+
+    #[test]
+    fn template_errors_name_the_placeholder() {
+        let err = expand("code {nope}").unwrap_err();
+        assert!(
+            err.contains("`{nope}`") && err.contains("{dest}, {dir}"),
+            "{err}"
+        );
+        assert!(expand("code {dest").unwrap_err().contains("unclosed"));
+    }
+
+    fn split(command: &str) -> Vec<String> {
+        split_command(command).unwrap()
+    }
+
+    #[test]
+    fn commands_are_split_into_words() {
+        assert_eq!(split("code  --wait\t-n"), ["code", "--wait", "-n"]);
+        assert_eq!(
+            split(r#""C:\Program Files\Tool\x.exe" /a"#),
+            [r"C:\Program Files\Tool\x.exe", "/a"]
+        );
+        assert_eq!(
+            split(r#"open -a 'Visual Studio Code' "it's" ''"#),
+            ["open", "-a", "Visual Studio Code", "it's", ""]
+        );
+        assert_eq!(split(r#"say "a \"b\"""#), ["say", r#"a "b""#]);
+        assert!(split_command("open 'a").is_err());
+        assert!(split_command(r#"open "a"#).is_err());
+        assert!(split("").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn backslashes_escape_outside_quotes() {
+        assert_eq!(split(r"open My\ Files it\'s"), ["open", "My Files", "it's"]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn backslashes_separate_paths_on_windows() {
+        assert_eq!(
+            split(r"C:\Tools\x.exe \\server\share"),
+            [r"C:\Tools\x.exe", r"\\server\share"]
+        );
+    }
+
+    const ALL_TYPES: [OutputType; 8] = [
+        OutputType::Plain,
+        OutputType::Posix,
+        OutputType::Zsh,
+        OutputType::Fish,
+        OutputType::PowerShell,
+        OutputType::Nushell,
+        OutputType::Elvish,
+        OutputType::Xonsh,
+    ];
+
+    fn tricky_paths() -> Vec<OsString> {
+        let paths = [
+            "",
+            "/home/me/my projects",
+            "/tmp/it's",
+            "/tmp/\"quoted\"",
+            "/tmp/$HOME and ${x}",
+            "/tmp/`id`",
+            "/tmp/a\nb\n",
+            "-rf",
+            "/tmp/back\\slash\\",
+            "/tmp/tab\tand ‘curly’",
+        ];
+        #[allow(unused_mut)]
+        let mut paths: Vec<_> = paths.iter().map(OsString::from).collect();
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStringExt;
+            paths.push(OsString::from_vec(b"/tmp/caf\xe9".to_vec()));
+            paths.push(OsString::from_vec(b"\xff\xfe-\x80\n".to_vec()));
+        }
+        paths
+    }
+
+    // Characters that mean something to one shell or another
+    const SPECIAL_CHARS: &[char] = &[
+        '\'', '"', '\\', '$', '`', '{', '}', '(', ')', '[', ']', '~', '*', '?', '#', ';', '&', '|',
+        '<', '>', '!', '%', '^', '=', '-', ' ', '\t', '\n', '\r', '\u{7}', '\u{1b}', '\u{85}',
+        '\u{2018}', '\u{2019}', '\u{201a}', '\u{201b}', '\u{fffd}', 'é', '😀',
+    ];
+
+    // Arbitrary paths, which never contain NUL
+    fn paths() -> BoxedStrategy<OsString> {
+        let chars = prop_oneof![
+            2 => any::<char>(),
+            3 => prop::sample::select(SPECIAL_CHARS),
+        ];
+        let piece = chars.prop_map(|c| c.to_string().into_bytes());
+        // Bytes that aren't valid UTF-8 on their own
+        #[cfg(unix)]
+        let piece = prop_oneof![4 => piece, 1 => (0x80..=0xffu8).prop_map(|byte| vec![byte])];
+        prop::collection::vec(piece, 0..24)
+            .prop_map(|pieces| {
+                let mut bytes = pieces.concat();
+                bytes.retain(|byte| *byte != 0);
+                #[cfg(unix)]
+                {
+                    use std::os::unix::ffi::OsStringExt;
+                    OsString::from_vec(bytes)
+                }
+                #[cfg(not(unix))]
+                OsString::from(String::from_utf8(bytes).unwrap())
+            })
+            .boxed()
+    }
+
+    fn is_lossless(out_type: OutputType) -> bool {
+        matches!(
+            out_type,
+            OutputType::Plain | OutputType::Posix | OutputType::Zsh | OutputType::Fish
+        )
+    }
+
+    // What the shell should get back: the shells that can't take invalid UTF-8 get the lossy text
+    fn expected_bytes(path: &OsStr, out_type: OutputType) -> Vec<u8> {
+        #[cfg(unix)]
+        if is_lossless(out_type) {
+            use std::os::unix::ffi::OsStrExt;
+            return path.as_bytes().to_vec();
+        }
+        let _ = is_lossless(out_type);
+        path.to_string_lossy().into_owned().into_bytes()
+    }
+
+    // Read a quoted word the way the shell does, as far as the constructs `quote` writes go, and
+    // fail on anything else, e.g. an unquoted character that would be special
+    fn unquote(word: &str, out_type: OutputType) -> Result<Vec<u8>, String> {
+        let mut out = vec![];
+        let mut rest = word;
+        let push = |out: &mut Vec<u8>, c: char| out.extend(c.to_string().as_bytes());
+        // The closing quote of a single-quoted string where nothing is special, and what's after
+        let literal = |rest: &str, quote: char| -> Result<(String, usize), String> {
+            let end = rest.find(quote).ok_or("unclosed quote")?;
+            Ok((rest[..end].to_string(), end + quote.len_utf8()))
+        };
+        while let Some(c) = rest.chars().next() {
+            let after = &rest[c.len_utf8()..];
+            match (out_type, c) {
+                (OutputType::Plain | OutputType::Posix | OutputType::Zsh, '\'') => {
+                    let (text, len) = literal(after, '\'')?;
+                    out.extend(text.as_bytes());
+                    rest = &after[len..];
+                }
+                (OutputType::Plain | OutputType::Posix | OutputType::Zsh, '\\') => {
+                    let escaped = after.chars().next().ok_or("trailing backslash")?;
+                    push(&mut out, escaped);
+                    rest = &after[escaped.len_utf8()..];
+                }
+                (OutputType::Plain | OutputType::Posix, '"') => {
+                    let octal = after
+                        .strip_prefix("$(printf '\\")
+                        .ok_or("unexpected double quote")?;
+                    let byte = u8::from_str_radix(&octal[..3], 8).map_err(|e| e.to_string())?;
+                    out.push(byte);
+                    rest = octal[3..].strip_prefix("')\"").ok_or("unclosed printf")?;
+                }
+                (OutputType::Zsh, '$') => {
+                    let hex = after.strip_prefix("'\\x").ok_or("unexpected $")?;
+                    let byte = u8::from_str_radix(&hex[..2], 16).map_err(|e| e.to_string())?;
+                    out.push(byte);
+                    rest = hex[2..].strip_prefix('\'').ok_or("unclosed $'")?;
+                }
+                (OutputType::Fish, '\'') => {
+                    let mut chars = after.char_indices();
+                    loop {
+                        match chars.next().ok_or("unclosed quote")? {
+                            (_, '\\') => match chars.next() {
+                                Some((_, escaped @ ('\\' | '\''))) => push(&mut out, escaped),
+                                _ => out.push(b'\\'),
+                            },
+                            (idx, '\'') => {
+                                rest = &after[idx + 1..];
+                                break;
+                            }
+                            (_, c) => push(&mut out, c),
+                        }
+                    }
+                }
+                (OutputType::Fish, '\\') => {
+                    let hex = after.strip_prefix('X').ok_or("unexpected backslash")?;
+                    out.push(u8::from_str_radix(&hex[..2], 16).map_err(|e| e.to_string())?);
+                    rest = &hex[2..];
+                }
+                (OutputType::PowerShell, '\'') => {
+                    let is_quote =
+                        |c| matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}');
+                    let mut chars = after.char_indices().peekable();
+                    loop {
+                        match chars.next().ok_or("unclosed quote")? {
+                            (_, c) if is_quote(c) => match chars.peek() {
+                                Some(&(_, next)) if is_quote(next) => {
+                                    push(&mut out, next);
+                                    chars.next();
+                                }
+                                _ => {
+                                    rest = chars.peek().map_or("", |&(idx, _)| &after[idx..]);
+                                    break;
+                                }
+                            },
+                            (_, c) => push(&mut out, c),
+                        }
+                    }
+                }
+                (OutputType::Nushell | OutputType::Xonsh, quote)
+                    if quote
+                        == if matches!(out_type, OutputType::Nushell) {
+                            '"'
+                        } else {
+                            '\''
+                        } =>
+                {
+                    let mut chars = after.char_indices();
+                    loop {
+                        match chars.next().ok_or("unclosed quote")? {
+                            (_, '\\') => match chars.next().ok_or("trailing backslash")?.1 {
+                                'n' => out.push(b'\n'),
+                                'r' => out.push(b'\r'),
+                                't' => out.push(b'\t'),
+                                'x' if matches!(out_type, OutputType::Xonsh) => {
+                                    let hex: String =
+                                        chars.by_ref().take(2).map(|(_, c)| c).collect();
+                                    let code =
+                                        u32::from_str_radix(&hex, 16).map_err(|e| e.to_string())?;
+                                    push(&mut out, char::from_u32(code).unwrap());
+                                }
+                                'u' if matches!(out_type, OutputType::Nushell) => {
+                                    let hex: String = chars
+                                        .by_ref()
+                                        .map(|(_, c)| c)
+                                        .skip(1)
+                                        .take_while(|c| *c != '}')
+                                        .collect();
+                                    let code =
+                                        u32::from_str_radix(&hex, 16).map_err(|e| e.to_string())?;
+                                    push(&mut out, char::from_u32(code).ok_or("bad code point")?);
+                                }
+                                escaped @ ('\\' | '"' | '\'') => push(&mut out, escaped),
+                                escaped => return Err(format!("unknown escape \\{escaped}")),
+                            },
+                            (idx, c) if c == quote => {
+                                rest = &after[idx + 1..];
+                                break;
+                            }
+                            (_, c) if c.is_control() => {
+                                return Err(format!("raw control character {c:?}"))
+                            }
+                            (_, c) => push(&mut out, c),
+                        }
+                    }
+                }
+                (OutputType::Elvish, '\'') => {
+                    let mut chars = after.char_indices().peekable();
+                    loop {
+                        match chars.next().ok_or("unclosed quote")? {
+                            (_, '\'') if chars.peek().is_some_and(|&(_, c)| c == '\'') => {
+                                out.push(b'\'');
+                                chars.next();
+                            }
+                            (idx, '\'') => {
+                                rest = &after[idx + 1..];
+                                break;
+                            }
+                            (_, c) => push(&mut out, c),
+                        }
+                    }
+                }
+                (_, c) => return Err(format!("unquoted {c:?}")),
+            }
+        }
+        Ok(out)
+    }
+
+    proptest! {
+        #[test]
+        fn quoted_paths_unquote_to_themselves(path in paths()) {
+            for out_type in ALL_TYPES {
+                let quoted = quote(&path, out_type);
+                prop_assert_eq!(
+                    unquote(&quoted, out_type),
+                    Ok(expected_bytes(&path, out_type)),
+                    "{} for {:?}",
+                    quoted,
+                    out_type
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn quoting_of_each_shell() {
+        let cases: [(OutputType, [&str; 4]); 8] = [
+            (OutputType::Plain, ["''", r"'it'\''s'", "'a\nb'", r"'$x\'"]),
+            (OutputType::Posix, ["''", r"'it'\''s'", "'a\nb'", r"'$x\'"]),
+            (OutputType::Zsh, ["''", r"'it'\''s'", "'a\nb'", r"'$x\'"]),
+            (OutputType::Fish, ["''", r"'it\'s'", "'a\nb'", r"'$x\\'"]),
+            (
+                OutputType::PowerShell,
+                ["''", "'it''s'", "'a\nb'", r"'$x\'"],
+            ),
+            (
+                OutputType::Nushell,
+                ["\"\"", "\"it's\"", r#""a\nb""#, r#""$x\\""#],
+            ),
+            (OutputType::Elvish, ["''", "'it''s'", "'a\nb'", r"'$x\'"]),
+            (OutputType::Xonsh, ["''", r"'it\'s'", r"'a\nb'", r"'$x\\'"]),
+        ];
+        for (out_type, expected) in cases {
+            let quoted = ["", "it's", "a\nb", r"$x\"].map(|s| quote(s, out_type));
+            assert_eq!(quoted, expected, "{out_type:?}");
+        }
+        assert_eq!(quote("‘it’", OutputType::PowerShell), "'‘‘it’’'");
+        assert_eq!(quote("a\u{1b}", OutputType::Nushell), r#""a\u{1b}""#);
+        assert_eq!(quote("a\u{1b}", OutputType::Xonsh), r"'a\x1b'");
+    }
+
+    // How each shell prints `WORD` with no trailing newline
+    fn printer(shell: &str) -> (&'static [&'static str], &'static str) {
+        match shell {
+            "pwsh" => (&["-NoProfile", "-Command"], "[Console]::Out.Write(WORD)"),
+            "nu" => (&["-c"], "print -n WORD"),
+            "elvish" => (&["-c"], "print WORD"),
+            "xonsh" => (&["--no-rc", "-c"], "print(WORD, end='')"),
+            _ => (&["-c"], "printf %s WORD"),
+        }
+    }
+
+    fn which(program: &str) -> bool {
+        env::var_os("PATH")
+            .is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+    }
+
+    // Both the paths that broke quoting before and generated ones, fewer of them as each case
+    // starts a shell
+    #[cfg(unix)]
+    fn assert_shell_reads_back(shell: &str, out_type: OutputType) {
+        use proptest::test_runner::{Config, TestCaseError, TestRunner};
+
+        assert!(which(shell), "{shell} isn't installed");
+        let (args, print) = printer(shell);
+        let reads_back = |path: &OsStr| -> Result<(), String> {
+            let quoted = quote(path, out_type);
+            let output = Command::new(shell)
+                .args(args)
+                .arg(print.replace("WORD", &quoted))
+                .output()
+                .map_err(|err| err.to_string())?;
+            match output.stdout == expected_bytes(path, out_type) {
+                true => Ok(()),
+                false => Err(format!(
+                    "{shell} read back {quoted} as {:?}, stderr: {}",
+                    String::from_utf8_lossy(&output.stdout),
+                    String::from_utf8_lossy(&output.stderr)
+                )),
+            }
+        };
+
+        for path in tricky_paths() {
+            reads_back(&path).unwrap();
+        }
+        let mut runner = TestRunner::new(Config::with_cases(32));
+        runner
+            .run(&paths(), |path| {
+                reads_back(&path).map_err(TestCaseError::fail)
+            })
+            .unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sh_reads_back_quoted_paths() {
+        assert_shell_reads_back("sh", OutputType::Posix);
+    }
+
+    // The other shells aren't always installed, `cargo test -- --ignored` runs them
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "needs bash"]
+    fn bash_reads_back_quoted_paths() {
+        assert_shell_reads_back("bash", OutputType::Posix);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "needs dash"]
+    fn dash_reads_back_quoted_paths() {
+        assert_shell_reads_back("dash", OutputType::Plain);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "needs zsh"]
+    fn zsh_reads_back_quoted_paths() {
+        assert_shell_reads_back("zsh", OutputType::Zsh);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "needs fish"]
+    fn fish_reads_back_quoted_paths() {
+        assert_shell_reads_back("fish", OutputType::Fish);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "needs pwsh"]
+    fn pwsh_reads_back_quoted_paths() {
+        assert_shell_reads_back("pwsh", OutputType::PowerShell);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "needs nu"]
+    fn nu_reads_back_quoted_paths() {
+        assert_shell_reads_back("nu", OutputType::Nushell);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "needs elvish"]
+    fn elvish_reads_back_quoted_paths() {
+        assert_shell_reads_back("elvish", OutputType::Elvish);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "needs xonsh"]
+    fn xonsh_reads_back_quoted_paths() {
+        assert_shell_reads_back("xonsh", OutputType::Xonsh);
+    }
+
+    #[test]
+    fn quoted_paths_are_single_words() {
+        for out_type in ALL_TYPES {
+            let quoted = quote("-rf", out_type);
+            assert!(!quoted.starts_with('-'), "{quoted}");
+            let quoted = quote("a b\nc", out_type);
+            assert!(quoted.starts_with(['\'', '"']), "{quoted}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_utf8_is_escaped_portably() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = OsStr::from_bytes(b"caf\xe9");
+        assert_eq!(quote(path, OutputType::Posix), r#"'caf'"$(printf '\351')""#);
+        assert_eq!(quote(path, OutputType::Zsh), r"'caf'$'\xe9'");
+        assert_eq!(quote(path, OutputType::Fish), r"'caf'\Xe9");
+        assert_eq!(quote(path, OutputType::Nushell), "\"caf\u{fffd}\"");
     }
 }
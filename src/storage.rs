@@ -7,6 +7,8 @@ use tracing::info;
 
 use anyhow::{bail, Context, Result};
 
+use crate::config;
+
 static USER_DIRS: Lazy<UserDirs> = Lazy::new(|| {
     UserDirs::new().expect("Couldn't locate HOME. Please, make sure the shell is properly set up")
 });
@@ -43,9 +45,30 @@ pub fn simplify_path(path: &Path) -> &Path {
     dunce::simplified(path)
 }
 
-fn data_dir() -> PathBuf {
+pub fn home_dir() -> &'static Path {
+    USER_DIRS.home_dir()
+}
+
+/// Expand a leading `~` into the user's home directory.
+pub fn expand_home(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(home_rel_path) => USER_DIRS.home_dir().join(home_rel_path),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// The platform's data directory, where the config is looked for too.
+pub fn default_data_dir() -> PathBuf {
     PROJECT_DIRS.data_local_dir().to_path_buf()
 }
+
+fn data_dir() -> PathBuf {
+    match &config::get().data_dir {
+        Some(data_dir) => data_dir.clone(),
+        None => default_data_dir(),
+    }
+}
+
 pub async fn get_or_create_data_dir() -> Result<PathBuf> {
     let data_local_dir = data_dir();
 
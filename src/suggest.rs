@@ -0,0 +1,175 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use anyhow::Result;
+use tokio::fs;
+use tracing::info;
+
+use crate::{
+    add::default_name,
+    bookmarks::{read_bookmarks, Bookmark},
+    cli,
+    shell::{Output, OutputType},
+    storage::{friendly_path, home_dir, simplify_path},
+    visits::{read_visits, Visits},
+};
+
+// Directories visited fewer times than this aren't worth a bookmark yet
+const MIN_VISITS: u32 = 3;
+
+/// A directory visited often that isn't bookmarked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub dir: PathBuf,
+    /// Name the bookmark would get, like with `shellmark add`
+    pub name: String,
+    /// Whether there's a bookmark with this name already
+    pub name_taken: bool,
+    pub count: u32,
+}
+
+/// Directories visited often that aren't bookmarked, most frecent first.
+pub async fn find_suggestions(
+    bookmarks: &[Arc<Bookmark>],
+    visits: &Visits,
+    limit: usize,
+) -> Vec<Suggestion> {
+    let mut suggestions = vec![];
+    for visit in visits.by_frecency() {
+        if suggestions.len() == limit {
+            break;
+        }
+        let dir = simplify_path(&visit.dir);
+        // Home is always a keystroke away
+        if visit.count < MIN_VISITS
+            || dir == home_dir()
+            || bookmarks.iter().any(|bm| simplify_path(&bm.dest) == dir)
+        {
+            continue;
+        }
+        // Files are bookmarked when they are opened, and directories might be gone by now
+        if !fs::metadata(dir).await.is_ok_and(|meta| meta.is_dir()) {
+            continue;
+        }
+
+        let name = default_name(dir);
+        suggestions.push(Suggestion {
+            dir: visit.dir.clone(),
+            name_taken: bookmarks.iter().any(|bm| bm.name == name),
+            name,
+            count: visit.count,
+        });
+    }
+    suggestions
+}
+
+pub struct Suggestions(Vec<Suggestion>);
+
+impl Display for Suggestions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let width = self.0.iter().map(|s| s.name.chars().count()).max();
+        for suggestion in &self.0 {
+            write!(
+                f,
+                "{:width$}  {}  ({} visits)",
+                suggestion.name,
+                friendly_path(&suggestion.dir),
+                suggestion.count,
+                width = width.unwrap_or_default()
+            )?;
+            if suggestion.name_taken {
+                write!(f, "  the name is taken, add it with --name")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Output for Suggestions {
+    fn to_output(&self, _out_type: OutputType) -> Option<String> {
+        Some(format!("{self}"))
+    }
+}
+
+pub async fn suggest_cmd(opts: cli::SuggestCmd) -> Result<Option<Suggestions>> {
+    let bookmarks = read_bookmarks().await?;
+    let visits = read_visits().await?;
+    let suggestions = find_suggestions(&bookmarks, &visits, opts.limit).await;
+    if suggestions.is_empty() {
+        info!("No often visited directories to bookmark, see `shellmark plug --track`");
+        return Ok(None);
+    }
+    Ok(Some(Suggestions(suggestions)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::Path, process};
+
+    use super::*;
+    use crate::visits::Visit;
+
+    // Visited just now, or so long ago that the visits weigh the least
+    const NOW: u64 = 1_000_000_000;
+    const LONG_AGO: u64 = 0;
+
+    fn visit(dir: &Path, count: u32, last: u64) -> Visit {
+        Visit {
+            dir: dir.to_path_buf(),
+            count,
+            last,
+        }
+    }
+
+    #[tokio::test]
+    async fn frecent_dirs_that_arent_bookmarked_are_suggested() {
+        let root = env::temp_dir().join(format!("shellmark-suggest-{}", process::id()));
+        for dir in ["often", "old", "few", "bookmarked", "taken"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join("file"), "").unwrap();
+        let bookmarks = vec![
+            Arc::new(Bookmark::new("mine".into(), root.join("bookmarked"))),
+            Arc::new(Bookmark::new("taken".into(), root.join("elsewhere"))),
+        ];
+        let visits = Visits::new(
+            vec![
+                visit(&root.join("old"), 10, LONG_AGO),
+                visit(&root.join("taken"), 4, NOW),
+                visit(&root.join("often"), 5, NOW),
+                visit(&root.join("few"), MIN_VISITS - 1, NOW),
+                visit(&root.join("bookmarked"), 20, NOW),
+                visit(&root.join("gone"), 20, NOW),
+                visit(&root.join("file"), 20, NOW),
+                visit(home_dir(), 50, NOW),
+            ],
+            NOW,
+        );
+
+        let suggestions = find_suggestions(&bookmarks, &visits, 10).await;
+        let found: Vec<_> = suggestions
+            .iter()
+            .map(|s| (s.name.as_str(), s.name_taken, s.count))
+            .collect();
+        // 5 recent visits weigh more than 10 old ones
+        assert_eq!(
+            found,
+            [("often", false, 5), ("taken", true, 4), ("old", false, 10)]
+        );
+        assert_eq!(suggestions[0].dir, root.join("often"));
+
+        let top = find_suggestions(&bookmarks, &visits, 1).await;
+        assert_eq!(top, suggestions[..1]);
+
+        let listed = Suggestions(suggestions).to_string();
+        let taken_line = listed.lines().nth(1).unwrap();
+        assert!(taken_line.starts_with("taken  "), "{listed}");
+        assert!(taken_line.ends_with("(4 visits)  the name is taken, add it with --name"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,429 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    io,
+    path::{self, Path, PathBuf},
+    process,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use quick_xml::{
+    events::{BytesEnd, BytesText, Event},
+    Reader, Writer,
+};
+use tokio::fs;
+use tracing::info;
+
+use crate::{
+    bookmarks::{read_bookmarks, write_bookmarks, Bookmark, Source},
+    cli,
+    import::{
+        default_file, file_uri, href, merge_entries, parse_entries, place_dest, ImportFormat,
+    },
+    storage::{self, friendly_path},
+};
+
+// A new places file for KDE, with the namespaces it writes itself
+const XBEL_SKELETON: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE xbel>
+<xbel xmlns:bookmark="http://www.freedesktop.org/standards/desktop-bookmarks" xmlns:mime="http://www.freedesktop.org/standards/shared-mime-info" xmlns:kdepriv="http://www.kde.org/kdepriv">
+</xbel>
+"#;
+
+/// File managers whose sidebar bookmarks can be synced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Sidebar {
+    /// GTK file managers: Nautilus, Thunar, Nemo, Caja and others
+    Gtk,
+    /// KDE's places: Dolphin and the file dialogs
+    Xbel,
+}
+
+impl Sidebar {
+    fn format(self) -> ImportFormat {
+        match self {
+            Sidebar::Gtk => ImportFormat::Gtk,
+            Sidebar::Xbel => ImportFormat::Xbel,
+        }
+    }
+}
+
+pub async fn sync_cmd(opts: cli::SyncCmd) -> Result<()> {
+    let file = match opts.file {
+        Some(file) => path::absolute(file)?,
+        None => default_file(opts.with.format())?,
+    };
+    let content = if file.exists() {
+        fs::read_to_string(&file)
+            .await
+            .with_context(|| format!("Couldn't read {}", friendly_path(&file)))?
+    } else {
+        String::new()
+    };
+    let entries = parse_entries(opts.with.format(), &content)?;
+
+    // Directories on both sides after the last sync, the ones missing from one side since were
+    // removed there
+    let mut state = read_state().await?;
+    let state_key = file.to_string_lossy().into_owned();
+    let synced: HashSet<PathBuf> = state
+        .remove(&state_key)
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    let mut places = HashSet::new();
+    for entry in &entries {
+        if let Ok(dest) = fs::canonicalize(&entry.dest).await {
+            places.insert(dest);
+        }
+    }
+
+    // Shared and project bookmarks belong to others, they are left out unless asked for
+    let all_sources = opts.all_sources;
+    let in_sync = |bm: &Bookmark| all_sources || bm.source == Source::Personal;
+
+    let mut bookmarks = read_bookmarks().await?;
+    let before = bookmarks.len();
+    bookmarks.retain(|bm| {
+        let unplaced = in_sync(bm)
+            && bm.source.is_writable()
+            && synced.contains(&bm.dest)
+            && !places.contains(&bm.dest);
+        if unplaced {
+            info!("Removed {}: it's no longer in the sidebar", bm.name);
+        }
+        !unplaced
+    });
+    let unbookmarked = before - bookmarks.len();
+
+    let mut removed_places = HashSet::new();
+    let mut new_entries = vec![];
+    for entry in entries {
+        match fs::canonicalize(&entry.dest).await {
+            Ok(dest)
+                if synced.contains(&dest)
+                    && !bookmarks.iter().any(|bm| in_sync(bm) && bm.dest == dest) =>
+            {
+                info!(
+                    "Removed {} from the sidebar: it's no longer bookmarked",
+                    friendly_path(&dest)
+                );
+                places.remove(&dest);
+                removed_places.insert(entry.dest);
+            }
+            _ => new_entries.push(entry),
+        }
+    }
+
+    // The sidebar's places become bookmarks
+    let merged = merge_entries(&mut bookmarks, new_entries).await;
+
+    // And the bookmarked directories become places, files can't be ones
+    let mut missing = vec![];
+    for bm in &bookmarks {
+        if !in_sync(bm) || places.contains(&bm.dest) {
+            continue;
+        }
+        if fs::metadata(&bm.dest).await.is_ok_and(|meta| meta.is_dir()) {
+            missing.push(bm.as_ref());
+        }
+    }
+
+    // The places file is checked before anything is written, and only written when the bookmarks
+    // were, so that a failure leaves both sides as they were
+    let new_content = if !missing.is_empty() || !removed_places.is_empty() {
+        match opts.with {
+            Sidebar::Gtk => Some(update_gtk_places(&content, &removed_places, &missing)),
+            Sidebar::Xbel => Some(
+                update_xbel_places(&content, &removed_places, &missing)
+                    .with_context(|| format!("Couldn't update {}", friendly_path(&file)))?,
+            ),
+        }
+    } else {
+        None
+    };
+    if merged.imported > 0 || unbookmarked > 0 {
+        write_bookmarks(&bookmarks).await?;
+    }
+    if let Some(new_content) = new_content {
+        write_places(&file, new_content).await?;
+    }
+    places.extend(missing.iter().map(|bm| bm.dest.clone()));
+    merged.report(false);
+    info!(
+        "Added {} and removed {} places in {}",
+        missing.len(),
+        removed_places.len(),
+        friendly_path(&file)
+    );
+
+    let mut synced: Vec<_> = bookmarks
+        .iter()
+        .filter(|bm| in_sync(bm))
+        .map(|bm| bm.dest.clone())
+        .filter(|dest| places.contains(dest))
+        .collect();
+    synced.sort();
+    synced.dedup();
+    state.insert(state_key, synced);
+    write_state(&state).await
+}
+
+// Drop the removed places, and add a line for each new one with a label that is the rest of it
+fn update_gtk_places(content: &str, removed: &HashSet<PathBuf>, added: &[&Bookmark]) -> String {
+    let mut out = String::with_capacity(content.len());
+    for line in content.lines() {
+        let uri = line.split_whitespace().next().unwrap_or_default();
+        if place_dest(uri).is_some_and(|dest| removed.contains(&dest)) {
+            continue;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    for bm in added {
+        let label = bm.name.replace(['\n', '\r'], " ");
+        out.push_str(&format!("{} {}\n", file_uri(&bm.dest), label));
+    }
+    out
+}
+
+// Drop the removed places and add the new ones at the end, everything else stays as it was
+fn update_xbel_places(
+    content: &str,
+    removed: &HashSet<PathBuf>,
+    added: &[&Bookmark],
+) -> Result<String> {
+    let content = if content.trim().is_empty() {
+        XBEL_SKELETON
+    } else {
+        content
+    };
+    let is_removed = |href: Option<String>| {
+        href.and_then(|href| place_dest(&href))
+            .is_some_and(|dest| removed.contains(&dest))
+    };
+
+    let mut reader = Reader::from_str(content);
+    let mut writer = Writer::new(vec![]);
+    let mut has_root = false;
+    // The indentation before a place, which goes when the place is removed
+    let mut space = None;
+    loop {
+        let event = reader.read_event()?;
+        let removed_place = match &event {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"bookmark" => {
+                is_removed(href(e)?)
+            }
+            _ => false,
+        };
+        if removed_place {
+            if let Event::Start(e) = &event {
+                reader.read_to_end(e.name())?;
+            }
+            space = None;
+            continue;
+        }
+        if let Event::Text(text) = &event {
+            if text.iter().all(u8::is_ascii_whitespace) {
+                if let Some(space) = space.replace(event) {
+                    writer.write_event(space)?;
+                }
+                continue;
+            }
+        }
+        if let Some(space) = space.take() {
+            writer.write_event(space)?;
+        }
+        match event {
+            Event::End(e) if e.local_name().as_ref() == b"xbel" => {
+                write_xbel_places(&mut writer, added)?;
+                writer.write_event(Event::End(e))?;
+                has_root = true;
+            }
+            // An empty places file can be a lone `<xbel/>`
+            Event::Empty(e) if e.local_name().as_ref() == b"xbel" => {
+                let end = BytesEnd::new(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+                writer.write_event(Event::Start(e))?;
+                writer.write_event(Event::Text(BytesText::from_escaped("\n")))?;
+                write_xbel_places(&mut writer, added)?;
+                writer.write_event(Event::End(end))?;
+                has_root = true;
+            }
+            Event::Eof => break,
+            event => writer.write_event(event)?,
+        }
+    }
+    if !has_root {
+        bail!("There is no <xbel> element");
+    }
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+fn write_xbel_places(writer: &mut Writer<Vec<u8>>, added: &[&Bookmark]) -> io::Result<()> {
+    for bm in added {
+        let uri = file_uri(&bm.dest);
+        writer.write_event(Event::Text(BytesText::from_escaped(" ")))?;
+        writer
+            .create_element("bookmark")
+            .with_attribute(("href", uri.as_str()))
+            .write_inner_content(|writer| {
+                writer.write_event(Event::Text(BytesText::from_escaped("\n  ")))?;
+                writer
+                    .create_element("title")
+                    .write_text_content(BytesText::new(&bm.name))?;
+                writer.write_event(Event::Text(BytesText::from_escaped("\n ")))
+            })?;
+        writer.write_event(Event::Text(BytesText::from_escaped("\n")))?;
+    }
+    Ok(())
+}
+
+// The file manager reads the file any time, so it's replaced at once. A link to it, e.g. from a
+// dotfiles repository, stays one
+async fn write_places(file: &Path, content: String) -> Result<()> {
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    let file = fs::canonicalize(file)
+        .await
+        .unwrap_or_else(|_| file.to_path_buf());
+    let mut tmp_file = file.clone().into_os_string();
+    tmp_file.push(format!(".{}", process::id()));
+    let tmp_file = PathBuf::from(tmp_file);
+
+    let written = match fs::write(&tmp_file, content).await {
+        Ok(()) => fs::rename(&tmp_file, &file).await,
+        Err(err) => Err(err),
+    };
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp_file).await;
+    }
+    written.with_context(|| format!("Couldn't write {}", friendly_path(&file)))
+}
+
+// The directories synced last time for each places file
+type SyncState = BTreeMap<String, Vec<PathBuf>>;
+
+async fn state_file() -> Result<PathBuf> {
+    Ok(storage::get_or_create_data_dir().await?.join("sync.json"))
+}
+
+async fn read_state() -> Result<SyncState> {
+    let state_file = state_file().await?;
+    if !state_file.exists() {
+        return Ok(SyncState::new());
+    }
+    let content = fs::read_to_string(&state_file)
+        .await
+        .with_context(|| format!("Couldn't read {}", friendly_path(&state_file)))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Couldn't parse {}", friendly_path(&state_file)))
+}
+
+async fn write_state(state: &SyncState) -> Result<()> {
+    let state_file = state_file().await?;
+    let content = serde_json::to_string_pretty(state).context("Couldn't serialize sync state")?;
+    let tmp_file = state_file.with_extension("json.tmp");
+    fs::write(&tmp_file, content).await?;
+    fs::rename(&tmp_file, &state_file)
+        .await
+        .with_context(|| format!("Couldn't write {}", friendly_path(&state_file)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn removed(dests: &[&str]) -> HashSet<PathBuf> {
+        dests.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn gtk_places_are_removed_and_added() {
+        let content = "file:///home/me/src Source\nfile:///tmp\nsftp://host/dir Remote\n";
+        let bm = Bookmark::new("R&D".into(), PathBuf::from("/srv/r&d"));
+        assert_eq!(
+            update_gtk_places(content, &removed(&["/tmp"]), &[&bm]),
+            "file:///home/me/src Source\nsftp://host/dir Remote\nfile:///srv/r%26d R&D\n"
+        );
+        assert_eq!(
+            update_gtk_places("", &removed(&[]), &[&bm]),
+            "file:///srv/r%26d R&D\n"
+        );
+    }
+
+    #[test]
+    fn xbel_places_are_removed_and_added() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE xbel>
+<xbel>
+ <!-- kept as it is -->
+ <bookmark href="file:///tmp">
+  <title>Temp</title>
+ </bookmark>
+ <bookmark href="file:///home/me/src" icon="folder"><title>Source &amp; tests</title></bookmark>
+ <bookmark href="file:///var/log"/>
+</xbel>
+"#;
+        let bm = Bookmark::new("R&D <team>".into(), PathBuf::from("/srv/r&d"));
+        let updated = update_xbel_places(content, &removed(&["/tmp", "/var/log"]), &[&bm]).unwrap();
+        assert_eq!(
+            updated,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE xbel>
+<xbel>
+ <!-- kept as it is -->
+ <bookmark href="file:///home/me/src" icon="folder"><title>Source &amp; tests</title></bookmark>
+ <bookmark href="file:///srv/r%26d">
+  <title>R&amp;D &lt;team&gt;</title>
+ </bookmark>
+</xbel>
+"#
+        );
+        let entries = parse_entries(ImportFormat::Xbel, &updated).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].name.as_deref(), Some("R&D <team>"));
+    }
+
+    #[test]
+    fn empty_xbel_files_keep_their_root() {
+        let bm = Bookmark::new("src".into(), PathBuf::from("/src"));
+        assert_eq!(
+            update_xbel_places(r#"<xbel version="1.0"/>"#, &removed(&[]), &[&bm]).unwrap(),
+            "<xbel version=\"1.0\">\n <bookmark href=\"file:///src\">\n  <title>src</title>\n </bookmark>\n</xbel>"
+        );
+        let new = update_xbel_places("", &removed(&[]), &[&bm]).unwrap();
+        assert!(new.starts_with(XBEL_SKELETON.lines().next().unwrap()));
+        assert!(new.contains("<title>src</title>"));
+    }
+
+    #[test]
+    fn broken_xbel_files_are_left_alone() {
+        let bm = Bookmark::new("src".into(), PathBuf::from("/src"));
+        assert!(update_xbel_places("<xbel><bookmark></xbel>", &removed(&[]), &[&bm]).is_err());
+        assert!(update_xbel_places("not xml", &removed(&[]), &[&bm]).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn places_are_replaced_through_links() {
+        let root = env::temp_dir().join(format!("shellmark-places-{}", process::id()));
+        std::fs::create_dir_all(root.join("dotfiles")).unwrap();
+        let target = root.join("dotfiles").join("bookmarks");
+        let link = root.join("bookmarks");
+        std::fs::write(&target, "file:///old\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_places(&link, "file:///new\n".to_string())
+            .await
+            .unwrap();
+        assert!(std::fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "file:///new\n");
+        assert_eq!(std::fs::read_dir(root.join("dotfiles")).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::{cli, storage};
+
+// Keep the visits file small, the least frecent directories are dropped first
+const VISITS_LIMIT: usize = 1000;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+/// How often and how recently a directory was visited.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Visit {
+    pub dir: PathBuf,
+    pub count: u32,
+    /// Seconds since the Unix epoch
+    pub last: u64,
+}
+
+impl Visit {
+    /// Visit count weighted by how recent the last visit was, like z and zoxide do.
+    pub fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last);
+        let weight = if age < HOUR {
+            4.0
+        } else if age < DAY {
+            2.0
+        } else if age < WEEK {
+            0.5
+        } else {
+            0.25
+        };
+        self.count as f64 * weight
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Visits {
+    visits: Vec<Visit>,
+    now: u64,
+    // summed up for every directory once, as bookmarks are ranked on every keystroke
+    frecency: HashMap<PathBuf, f64>,
+}
+
+impl Visits {
+    pub(crate) fn new(visits: Vec<Visit>, now: u64) -> Visits {
+        let mut frecency = HashMap::new();
+        for visit in &visits {
+            for dir in visit.dir.ancestors() {
+                *frecency.entry(dir.to_path_buf()).or_default() += visit.frecency(now);
+            }
+        }
+        Visits {
+            visits,
+            now,
+            frecency,
+        }
+    }
+
+    /// Frecency of a bookmark: the sum over the visited directories inside its destination.
+    pub fn frecency(&self, dest: &Path) -> f64 {
+        self.frecency.get(dest).copied().unwrap_or_default()
+    }
+
+    /// Visited directories, most frecent first.
+    pub fn by_frecency(&self) -> Vec<&Visit> {
+        let mut visits: Vec<_> = self.visits.iter().collect();
+        visits.sort_by(|a, b| b.frecency(self.now).total_cmp(&a.frecency(self.now)));
+        visits
+    }
+}
+
+fn visits_file(data_dir: &Path) -> PathBuf {
+    data_dir.join("visits.json")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Read the statistics of visited directories.
+pub async fn read_visits() -> Result<Visits> {
+    let data_dir = storage::get_or_create_data_dir().await?;
+    let visits = read_visits_intern(&visits_file(&data_dir)).await?;
+    Ok(Visits::new(visits, now()))
+}
+
+/// Count a visit of the directory or the file.
+pub async fn record_visit(path: &Path) -> Result<()> {
+    let path = fs::canonicalize(path)
+        .await
+        .with_context(|| format!("Couldn't resolve {}", path.display()))?;
+    // The visits are stored as JSON text
+    if path.to_str().is_none() {
+        return Ok(());
+    }
+
+    let data_dir = storage::get_or_create_data_dir().await?;
+    // Shell hooks record visits in the background, so two quick `cd`s would lose one otherwise
+    let _lock = lock_visits(&data_dir).await?;
+    let visits_file = visits_file(&data_dir);
+    let mut visits = read_visits_intern(&visits_file).await?;
+    let now = now();
+    match visits.iter_mut().find(|visit| visit.dir == path) {
+        Some(visit) => {
+            visit.count = visit.count.saturating_add(1);
+            visit.last = now;
+        }
+        None => visits.push(Visit {
+            dir: path,
+            count: 1,
+            last: now,
+        }),
+    }
+    if visits.len() > VISITS_LIMIT {
+        visits.sort_by(|a, b| b.frecency(now).total_cmp(&a.frecency(now)));
+        visits.truncate(VISITS_LIMIT);
+    }
+
+    write_visits_intern(&visits_file, &visits).await
+}
+
+pub async fn visit_cmd(opts: cli::VisitCmd) -> Result<()> {
+    record_visit(Path::new(&opts.dir)).await
+}
+
+// Held until the returned file is dropped
+async fn lock_visits(data_dir: &Path) -> Result<std::fs::File> {
+    let lock_file = data_dir.join("visits.lock");
+    let lock = tokio::task::spawn_blocking(move || {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_file)?;
+        file.lock()?;
+        Ok::<_, std::io::Error>(file)
+    });
+    lock.await?.context("Couldn't lock the visits file")
+}
+
+async fn read_visits_intern(visits_file: &Path) -> Result<Vec<Visit>> {
+    if !visits_file.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(visits_file)
+        .await
+        .with_context(|| format!("Couldn't read visits file: {}", visits_file.display()))?;
+
+    if content.trim().is_empty() {
+        Ok(Vec::new())
+    } else {
+        serde_json::from_str(&content).context("Couldn't parse visits JSON")
+    }
+}
+
+async fn write_visits_intern(visits_file: &Path, visits: &[Visit]) -> Result<()> {
+    let content = serde_json::to_string(visits).context("Couldn't serialize visits to JSON")?;
+    // Readers don't take the lock, so never leave a half-written file to them
+    let tmp_file = visits_file.with_extension(format!("json.{}", std::process::id()));
+    fs::write(&tmp_file, content).await?;
+    Ok(fs::rename(&tmp_file, visits_file).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visit(dir: &str, count: u32, last: u64) -> Visit {
+        Visit {
+            dir: PathBuf::from(dir),
+            count,
+            last,
+        }
+    }
+
+    #[test]
+    fn recent_visits_weigh_more() {
+        let now = 100 * WEEK;
+        assert_eq!(visit("/a", 3, now - 60).frecency(now), 12.0);
+        assert_eq!(visit("/a", 3, now - 2 * HOUR).frecency(now), 6.0);
+        assert_eq!(visit("/a", 3, now - 2 * DAY).frecency(now), 1.5);
+        assert_eq!(visit("/a", 3, now - 2 * WEEK).frecency(now), 0.75);
+    }
+
+    #[test]
+    fn bookmark_frecency_sums_the_dirs_inside() {
+        let now = 100 * WEEK;
+        let visits = Visits::new(
+            vec![
+                visit("/src/app", 1, now),
+                visit("/src/app/lib", 2, now),
+                visit("/src/application", 5, now),
+            ],
+            now,
+        );
+        assert_eq!(visits.frecency(Path::new("/src/app")), 12.0);
+        assert_eq!(visits.frecency(Path::new("/src/app/lib")), 8.0);
+        assert_eq!(visits.frecency(Path::new("/src")), 32.0);
+        assert_eq!(visits.frecency(Path::new("/elsewhere")), 0.0);
+    }
+}